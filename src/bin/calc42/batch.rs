@@ -0,0 +1,164 @@
+use std::io::{self, BufRead, Write};
+use std::str::FromStr;
+use calculate42::{evaluate, Answer, CalcContext, CalcError};
+
+/// How results of a batch are written
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Output {
+    /// The result or the error of every expression in its own line
+    Plain,
+    /// Table with the header: line,expression,result,error_type,error_message
+    Csv,
+    /// JSON object for every expression in its own line
+    Json
+}
+
+impl FromStr for Output {
+    type Err = String;
+
+    fn from_str(name: &str) -> Result<Output, String> {
+        match name {
+            "plain" => Ok(Output::Plain),
+            "csv" => Ok(Output::Csv),
+            "json" => Ok(Output::Json),
+            _ => Err(format!("Unknown output: {}. Use plain, csv or json.", name))
+        }
+    }
+}
+
+/// Calculates expressions line by line. Empty lines and comments after `#` are skipped,
+/// variables and functions of the previous lines can be used in the next ones.
+/// Returns false, if any expression is wrong.
+pub fn run(input: impl BufRead, output: Output, context: &mut CalcContext, writer: &mut impl Write) -> io::Result<bool> {
+    let mut is_ok = true;
+    if output == Output::Csv {
+        writeln!(writer, "line,expression,result,error_type,error_message")?;
+    }
+
+    for (i, line) in input.lines().enumerate() {
+        let line = line?;
+        let expression = line.split('#').next().unwrap_or_default().trim();
+        if expression.is_empty() { continue }
+
+        let result = calculate(expression, context);
+        is_ok &= result.is_ok();
+        writeln!(writer, "{}", format_result(output, i + 1, expression, &result))?;
+    }
+    Ok(is_ok)
+}
+
+/// Calculates an expression and renders the answer. Results are remembered, so the next expressions can use `ans`.
+pub fn calculate(expression: &str, context: &mut CalcContext) -> Result<String, CalcError> {
+    match evaluate(expression, context)? {
+        Answer::Number(n) => {
+            let text = context.format().render(&n);
+            context.push_result(n);
+            Ok(text)
+        },
+        Answer::Formatted(n, format) => {
+            let text = format.render(&n);
+            context.push_result(n);
+            Ok(text)
+        },
+        Answer::Function(name) => Ok(format!("Function {} is defined.", name))
+    }
+}
+
+fn format_result(output: Output, line: usize, expression: &str, result: &Result<String, CalcError>) -> String {
+    match (output, result) {
+        (Output::Plain, Ok(text)) => text.clone(),
+        (Output::Plain, Err(e)) => format!("{:?}: {}", e.error_type(), e),
+        (Output::Csv, Ok(text)) => format!("{},{},{},,", line, csv_field(expression), csv_field(text)),
+        (Output::Csv, Err(e)) => format!("{},{},,{:?},{}", line, csv_field(expression), e.error_type(), csv_field(&e.to_string())),
+        (Output::Json, Ok(text)) => format!(r#"{{"line":{},"expression":{},"result":{}}}"#, line, json_string(expression), json_string(text)),
+        (Output::Json, Err(e)) => format!(r#"{{"line":{},"expression":{},"error_type":"{:?}","error_message":{}}}"#,
+            line, json_string(expression), e.error_type(), json_string(&e.to_string()))
+    }
+}
+
+/// A field with a comma, a quote or a line break is quoted, quotes inside are doubled
+fn csv_field(text: &str) -> String {
+    if text.contains([',', '"', '\n', '\r']) { format!("\"{}\"", text.replace('"', "\"\"")) }
+    else { String::from(text) }
+}
+
+fn json_string(text: &str) -> String {
+    let mut json = String::from("\"");
+    for ch in text.chars() {
+        match ch {
+            '"' => json.push_str("\\\""),
+            '\\' => json.push_str("\\\\"),
+            '\n' => json.push_str("\\n"),
+            '\t' => json.push_str("\\t"),
+            control if control.is_control() => json.push_str(&format!("\\u{:04x}", control as u32)),
+            _ => json.push(ch)
+        }
+    }
+    json.push('"');
+    json
+}
+
+#[cfg(test)]
+mod tests {
+    const INPUT: &str = "# Prices\nx = 2,5 * 4\n\nx / 0\nmax(x, 3) # the biggest\nf(a) = a^2\nf(ans)\nans -\r90\n";
+
+    fn run_to_string(output: super::Output) -> (bool, String) {
+        let mut context = super::CalcContext::default();
+        let mut written = Vec::new();
+        let is_ok = super::run(INPUT.as_bytes(), output, &mut context, &mut written).unwrap();
+        (is_ok, String::from_utf8(written).unwrap())
+    }
+
+    #[test]
+    fn run_plain() {
+        use super::*;
+
+        assert_eq!(run_to_string(Output::Plain), (false, String::from("10\nDivisionByZero: Division by zero.\n10\nFunction f is defined.\n100\n10\n")));
+    }
+
+    #[test]
+    fn run_csv() {
+        use super::*;
+
+        assert_eq!(run_to_string(Output::Csv), (false, String::from(
+            "line,expression,result,error_type,error_message\n\
+            2,\"x = 2,5 * 4\",10,,\n\
+            4,x / 0,,DivisionByZero,Division by zero.\n\
+            5,\"max(x, 3)\",10,,\n\
+            6,f(a) = a^2,Function f is defined.,,\n\
+            7,f(ans),100,,\n\
+            8,\"ans -\r90\",10,,\n")));
+    }
+
+    #[test]
+    fn run_json() {
+        use super::*;
+
+        let (is_ok, text) = run_to_string(Output::Json);
+        assert!(!is_ok);
+        assert_eq!(text.lines().nth(1), Some(r#"{"line":4,"expression":"x / 0","error_type":"DivisionByZero","error_message":"Division by zero."}"#));
+        assert_eq!(text.lines().last(), Some(r#"{"line":8,"expression":"ans -\u000d90","result":"10"}"#));
+    }
+
+    #[test]
+    fn calculate_remembers_results() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(calculate("2 + 2", &mut context), Ok(String::from("4")));
+        assert_eq!(calculate("ans * 2", &mut context), Ok(String::from("8")));
+        assert_eq!(context.get_last_result(), Some(&calculate42::Number::from(8)));
+        assert!(calculate("1 +", &mut context).is_err());
+    }
+
+    #[test]
+    fn escape_fields() {
+        use super::*;
+
+        assert_eq!(csv_field("a \"b\""), "\"a \"\"b\"\"\"");
+        assert_eq!(csv_field("a\rb"), "\"a\rb\"");
+        assert_eq!(json_string("a \"b\" \\ \u{1}"), r#""a \"b\" \\ \u0001""#);
+        assert_eq!("csv".parse(), Ok(Output::Csv));
+        assert!("xml".parse::<Output>().is_err());
+    }
+}
@@ -0,0 +1,192 @@
+use std::fs::File;
+use std::io::{self, BufReader, IsTerminal};
+use std::path::PathBuf;
+use std::process::ExitCode;
+use calculate42::{Arithmetic, CalcContext, DEFAULT_PRECISION};
+use rustyline::error::ReadlineError;
+use rustyline::DefaultEditor;
+mod batch;
+
+use batch::{calculate, Output};
+
+const USAGE: &str = "Usage: calc42 [-e <expression>]... | [-o plain|csv|json] [<file>]
+Without arguments starts an interactive calculator, Ctrl+D exits it.
+Expressions from a file or from the piped input are calculated line by line, # starts a comment.
+  -e, --expression <expression>  calculate the expression, print the result and exit
+  -o, --output plain|csv|json    how results of a file are written, plain by default
+  -h, --help                     display this text";
+
+/// What the user asks the program to do
+#[derive(Debug, PartialEq)]
+enum Run {
+    Interactive,
+    /// Expressions from `-e` in the order they are given, they share variables
+    Expressions(Vec<String>),
+    /// Expressions from a file, or from the input, if there is no file
+    Batch(Option<String>, Output),
+    Help
+}
+
+fn main() -> ExitCode {
+    let run = match parse_args(std::env::args().skip(1)) {
+        Ok(run) => run,
+        Err(message) => {
+            eprintln!("{}\n{}", message, USAGE);
+            return ExitCode::from(2)
+        }
+    };
+
+    // The same numbers as in the bot: exact decimals
+    let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+
+    match run {
+        Run::Help => {
+            println!("{}", USAGE);
+            ExitCode::SUCCESS
+        },
+        Run::Expressions(expressions) => {
+            for expression in expressions {
+                match calculate(&expression, &mut context) {
+                    Ok(text) => println!("{}", text),
+                    Err(e) => {
+                        eprintln!("{}", e.render(&expression));
+                        return ExitCode::FAILURE
+                    }
+                }
+            }
+            ExitCode::SUCCESS
+        },
+        // Piped expressions are calculated without the line editor
+        Run::Interactive if !io::stdin().is_terminal() => calculate_batch(None, Output::Plain, &mut context),
+        Run::Interactive => repl(&mut context),
+        Run::Batch(file, output) => calculate_batch(file, output, &mut context)
+    }
+}
+
+fn parse_args(args: impl Iterator<Item = String>) -> Result<Run, String> {
+    let mut expressions = Vec::new();
+    let mut file = None;
+    let mut output = None;
+
+    let mut args = args.peekable();
+    while let Some(arg) = args.next() {
+        match arg.as_str() {
+            "-e" | "--expression" => match args.next() {
+                Some(expression) => expressions.push(expression),
+                None => return Err(format!("{} needs an expression.", arg))
+            },
+            "-o" | "--output" => match args.next() {
+                Some(name) => output = Some(name.parse()?),
+                None => return Err(format!("{} needs plain, csv or json.", arg))
+            },
+            "-h" | "--help" => return Ok(Run::Help),
+            name if file.is_none() && (name == "-" || !name.starts_with('-')) => file = Some(arg),
+            _ => return Err(format!("Unknown argument: {}", arg))
+        }
+    }
+
+    match (expressions.is_empty(), file, output) {
+        (false, None, None) => Ok(Run::Expressions(expressions)),
+        (false, _, _) => Err(String::from("Expressions can't be calculated together with a file.")),
+        (true, None, None) => Ok(Run::Interactive),
+        // - is the input
+        (true, file, output) => Ok(Run::Batch(file.filter(|name| name != "-"), output.unwrap_or(Output::Plain)))
+    }
+}
+
+/// Calculates every line of the file or of the input. Wrong lines don't stop it, but the exit code is a failure.
+fn calculate_batch(file: Option<String>, output: Output, context: &mut CalcContext) -> ExitCode {
+    let result = match &file {
+        Some(name) => File::open(name)
+            .and_then(|file| batch::run(BufReader::new(file), output, context, &mut io::stdout().lock())),
+        None => batch::run(io::stdin().lock(), output, context, &mut io::stdout().lock())
+    };
+
+    match result {
+        Ok(true) => ExitCode::SUCCESS,
+        Ok(false) => ExitCode::FAILURE,
+        Err(e) => {
+            eprintln!("Can't read {}: {}", file.as_deref().unwrap_or("the input"), e);
+            ExitCode::FAILURE
+        }
+    }
+}
+
+/// Reads expressions line by line, until the input ends. Errors don't stop it.
+fn repl(context: &mut CalcContext) -> ExitCode {
+    let mut editor = match DefaultEditor::new() {
+        Ok(editor) => editor,
+        Err(e) => {
+            eprintln!("Can't read the input: {}", e);
+            return ExitCode::FAILURE
+        }
+    };
+    let history = history_path();
+    if let Some(path) = &history {
+        // There is no history at the first start
+        let _ = editor.load_history(path);
+    }
+
+    loop {
+        match editor.readline("> ") {
+            Ok(line) => {
+                if line.trim().is_empty() { continue }
+                let _ = editor.add_history_entry(line.as_str());
+
+                match calculate(&line, context) {
+                    Ok(text) => println!("{}", text),
+                    Err(e) => eprintln!("{}", e.render(&line))
+                }
+            },
+            // Ctrl+C drops the line, Ctrl+D exits
+            Err(ReadlineError::Interrupted) => continue,
+            Err(ReadlineError::Eof) => break,
+            Err(e) => {
+                eprintln!("Can't read the input: {}", e);
+                return ExitCode::FAILURE
+            }
+        }
+    }
+
+    if let Some(path) = &history {
+        if let Err(e) = editor.save_history(path) {
+            eprintln!("Can't save the history to {}: {}", path.display(), e);
+        }
+    }
+    ExitCode::SUCCESS
+}
+
+fn history_path() -> Option<PathBuf> {
+    std::env::var_os("HOME").map(|home| PathBuf::from(home).join(".calc42_history"))
+}
+
+#[cfg(test)]
+mod tests {
+    fn args(args: &[&str]) -> impl Iterator<Item = String> {
+        args.iter().map(|arg| String::from(*arg)).collect::<Vec<String>>().into_iter()
+    }
+
+    #[test]
+    fn parse_args_correct() {
+        use super::*;
+
+        assert_eq!(parse_args(args(&[])), Ok(Run::Interactive));
+        assert_eq!(parse_args(args(&["-e", "2 + 2"])), Ok(Run::Expressions(vec![String::from("2 + 2")])));
+        assert_eq!(parse_args(args(&["-e", "x = 2", "--expression", "x * 3"])), Ok(Run::Expressions(vec![String::from("x = 2"), String::from("x * 3")])));
+        assert_eq!(parse_args(args(&["-e", "1", "--help"])), Ok(Run::Help));
+        assert_eq!(parse_args(args(&["prices.txt"])), Ok(Run::Batch(Some(String::from("prices.txt")), Output::Plain)));
+        assert_eq!(parse_args(args(&["-o", "json", "-"])), Ok(Run::Batch(None, Output::Json)));
+        assert_eq!(parse_args(args(&["--output", "csv"])), Ok(Run::Batch(None, Output::Csv)));
+    }
+
+    #[test]
+    fn parse_args_errors() {
+        use super::*;
+
+        assert_eq!(parse_args(args(&["-e"])), Err(String::from("-e needs an expression.")));
+        assert_eq!(parse_args(args(&["a.txt", "b.txt"])), Err(String::from("Unknown argument: b.txt")));
+        assert_eq!(parse_args(args(&["-x"])), Err(String::from("Unknown argument: -x")));
+        assert_eq!(parse_args(args(&["-o", "xml"])), Err(String::from("Unknown output: xml. Use plain, csv or json.")));
+        assert_eq!(parse_args(args(&["-e", "1", "a.txt"])), Err(String::from("Expressions can't be calculated together with a file.")));
+    }
+}
@@ -1,3 +1,10 @@
+use super::lexer::Span;
+
+/// Chars of a long expression, which are echoed before and after the problem place
+const ECHOED_CHARS: usize = 30;
+/// Carets under a long problem place
+const MAX_CARETS: usize = 60;
+
 #[derive(Clone, Copy, Debug, PartialEq)]
 pub enum ErrorType {
     NotMathExpr,
@@ -5,13 +12,23 @@ pub enum ErrorType {
     OperandNotNumber,
     MissedOperation,
     MissedOperand,
-    UnknownError
+    UnknownName,
+    UnknownFunction,
+    WrongArgumentsCount,
+    WrongDefinition,
+    RecursionTooDeep,
+    DivisionByZero,
+    Overflow,
+    DomainError,
+    LimitExceeded,
+    UnknownBase
 }
 
 #[derive(Debug, PartialEq)]
 pub struct Error {
     error_type: ErrorType,
-    details: String
+    details: String,
+    span: Option<Span>
 }
 
 impl Error {
@@ -23,11 +40,61 @@ impl Error {
                 ErrorType::OperandNotNumber => { String::from("One of operands is not a correct number.") },
                 ErrorType::MissedOperation => { String::from("Missed operation.") },
                 ErrorType::MissedOperand => { String::from("Missed operand.") },
-                ErrorType::UnknownError => { String::from("Unknown error.") }   
+                ErrorType::UnknownName => { String::from("Unknown name.") },
+                ErrorType::UnknownFunction => { String::from("Unknown function.") },
+                ErrorType::WrongArgumentsCount => { String::from("Wrong number of function arguments.") },
+                ErrorType::WrongDefinition => { String::from("Parameters of a function must be different names separated by commas.") },
+                ErrorType::RecursionTooDeep => { String::from("Too deep recursion of functions.") },
+                ErrorType::DivisionByZero => { String::from("Division by zero.") },
+                ErrorType::Overflow => { String::from("The result is too big.") },
+                ErrorType::DomainError => { String::from("The operation is not defined for these operands.") },
+                ErrorType::LimitExceeded => { String::from("The expression is too long or too hard to calculate.") },
+                ErrorType::UnknownBase => { String::from("The base must be from 2 to 36.") }
             },
-            error_type: error_type
+            error_type,
+            span: None
         }
     }
+
+    /// Creates an error which points at the problem place in the input
+    pub fn with_span(error_type: ErrorType, span: Span) -> Error {
+        Error { span: Some(span), ..Error::new(error_type) }
+    }
+
+    pub fn error_type(&self) -> ErrorType {
+        self.error_type
+    }
+
+    pub fn span(&self) -> Option<Span> {
+        self.span
+    }
+
+    /// Echoes the expression and underlines the problem place with carets, e.g.:
+    /// ```text
+    /// 2 + * 3
+    ///     ^ Missed operand.
+    /// ```
+    /// Without a span only the details are returned.
+    /// Only a part of a long expression around the problem place is echoed, so the text fits a message.
+    pub fn render(&self, math_expr: &str) -> String {
+        let span = match self.span {
+            Some(span) => span,
+            None => return self.details.clone()
+        };
+
+        // The span is in bytes, but the caret has to stand under a char
+        let start = math_expr.get(..span.start).map_or(0, |before| before.chars().count());
+        let width = math_expr.get(span.start..span.end).map_or(0, |inside| inside.chars().count()).clamp(1, MAX_CARETS);
+
+        let chars: Vec<char> = math_expr.chars().collect();
+        let first = start.saturating_sub(ECHOED_CHARS);
+        let last = (start + width + ECHOED_CHARS).min(chars.len());
+        let before = if first > 0 { "…" } else { "" };
+        let after = if last < chars.len() { "…" } else { "" };
+        let echo: String = chars[first..last].iter().collect();
+
+        format!("{}{}{}\n{}{} {}", before, echo, after, " ".repeat(before.chars().count() + start - first), "^".repeat(width), self.details)
+    }
 }
 
 impl std::fmt::Display for Error {
@@ -40,4 +107,60 @@ impl std::error::Error for Error {
     fn description(&self) -> &str {
         &self.details
     }
-}
\ No newline at end of file
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn render_without_span() {
+        use super::*;
+
+        let error = Error::new(ErrorType::MissedOperand);
+        assert_eq!(error.render("2 + * 3"), "Missed operand.");
+    }
+
+    #[test]
+    fn render_with_span() {
+        use super::*;
+
+        let error = Error::with_span(ErrorType::MissedOperand, Span { start: 4, end: 5 });
+        assert_eq!(error.render("2 + * 3"), "2 + * 3\n    ^ Missed operand.");
+    }
+
+    #[test]
+    fn render_long_span() {
+        use super::*;
+
+        let error = Error::with_span(ErrorType::OperandNotNumber, Span { start: 4, end: 8 });
+        assert_eq!(error.render("1 + 2..3"), "1 + 2..3\n    ^^^^ One of operands is not a correct number.");
+    }
+
+    #[test]
+    fn render_span_at_the_end() {
+        use super::*;
+
+        let error = Error::with_span(ErrorType::MissedOperand, Span { start: 3, end: 3 });
+        assert_eq!(error.render("2 +"), "2 +\n   ^ Missed operand.");
+    }
+
+    #[test]
+    fn render_long_expression() {
+        use super::*;
+
+        let math_expr = format!("{}* 3{}", "2 + ".repeat(1000), " + 2".repeat(1000));
+        let error = Error::with_span(ErrorType::MissedOperand, Span { start: 4000, end: 4001 });
+        assert_eq!(error.render(&math_expr), format!("…+ 2 + 2 + 2 + 2 + 2 + 2 + 2 + * 3 + 2 + 2 + 2 + 2 + 2 + 2 + 2…\n{}^ Missed operand.", " ".repeat(31)));
+
+        let error = Error::with_span(ErrorType::LimitExceeded, Span { start: 0, end: math_expr.len() });
+        let rendered = error.render(&math_expr);
+        assert!(rendered.starts_with(&format!("{}…\n{} ", &math_expr[..90], "^".repeat(60))), "{}", rendered);
+    }
+
+    #[test]
+    fn render_counts_chars_not_bytes() {
+        use super::*;
+
+        let error = Error::with_span(ErrorType::NotMathExpr, Span { start: 4, end: 6 });
+        assert_eq!(error.render("2 + ж"), "2 + ж\n    ^ Input is not a mathematical expression.");
+    }
+}
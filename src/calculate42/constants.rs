@@ -0,0 +1,44 @@
+/// Named constant, which can be used in an expression instead of a number
+#[derive(Debug, PartialEq)]
+pub struct Constant {
+    pub name: &'static str,
+    pub value: f64,
+    pub description: &'static str
+}
+
+pub const CONSTANTS: [Constant; 10] = [
+    Constant { name: "pi", value: std::f64::consts::PI, description: "ratio of a circle's circumference to its diameter" },
+    Constant { name: "e", value: std::f64::consts::E, description: "base of the natural logarithm" },
+    Constant { name: "tau", value: std::f64::consts::TAU, description: "2 * pi" },
+    Constant { name: "phi", value: 1.618033988749895, description: "golden ratio" },
+    Constant { name: "inf", value: f64::INFINITY, description: "infinity" },
+    Constant { name: "c", value: 299_792_458.0, description: "speed of light in vacuum, m/s" },
+    Constant { name: "g", value: 9.80665, description: "standard acceleration of gravity, m/s^2" },
+    Constant { name: "h", value: 6.626_070_15e-34, description: "Planck constant, J*s" },
+    Constant { name: "k_B", value: 1.380_649e-23, description: "Boltzmann constant, J/K" },
+    Constant { name: "N_A", value: 6.022_140_76e23, description: "Avogadro constant, 1/mol" }
+];
+
+pub fn find_constant(name: &str) -> Option<&'static Constant> {
+    CONSTANTS.iter().find(|constant| constant.name == name)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn find_constant_correct() {
+        use super::*;
+
+        assert_eq!(find_constant("pi").map(|constant| constant.value), Some(std::f64::consts::PI));
+        assert_eq!(find_constant("k_B").map(|constant| constant.value), Some(1.380649e-23));
+        assert_eq!(find_constant("PI"), None);
+        assert_eq!(find_constant("sin"), None);
+    }
+
+    #[test]
+    fn phi_is_golden_ratio() {
+        use super::*;
+
+        assert_eq!(find_constant("phi").map(|constant| constant.value), Some((1.0 + 5f64.sqrt()) / 2.0));
+    }
+}
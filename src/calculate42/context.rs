@@ -0,0 +1,304 @@
+use std::cell::Cell;
+use std::collections::{BTreeMap, VecDeque};
+use std::time::Instant;
+use super::format::Format;
+use super::limits::Limits;
+use super::locale::Locale;
+use super::number::{Arithmetic, Number};
+use super::parser::Expr;
+
+/// How many last results are remembered, so a long chat doesn't take more and more memory
+const MAX_RESULTS: usize = 1000;
+
+/// Function defined by the user like `f(x, y) = x^2 + y`
+#[derive(Clone, Debug, PartialEq)]
+pub(super) struct UserFunction {
+    pub parameters: Vec<String>,
+    pub body: Expr,
+    /// The definition as the user wrote it
+    pub text: String
+}
+
+/// Everything, that an expression can refer to besides built-in functions and constants.
+/// Variables shadow the previous result and constants with the same name.
+/// User functions shadow built-in functions with the same name.
+#[derive(Clone, Debug, Default, PartialEq)]
+pub struct Context {
+    variables: BTreeMap<String, Number>,
+    functions: BTreeMap<String, UserFunction>,
+    /// The last results, older ones are forgotten
+    results: VecDeque<Number>,
+    /// How many results are forgotten, so the numbers of the remembered ones stay the same
+    forgotten_results: usize,
+    arithmetic: Arithmetic,
+    format: Format,
+    /// Real operations without a real result, like sqrt(-1), give NaN instead of a complex number
+    real_only: bool,
+    /// Operations without a finite result give inf and NaN like IEEE 754 floats instead of errors
+    ieee: bool,
+    /// Operations can't be omitted: 2x is an error, only 2 * x is correct
+    strict: bool,
+    /// How numbers are written in expressions
+    locale: Locale,
+    limits: Limits,
+    /// Operations calculated for the current message. Calculation doesn't change the context otherwise,
+    /// so the counter can be changed through a shared reference.
+    steps: Cell<usize>,
+    /// When the calculation of the current message started
+    start: Cell<Option<Instant>>
+}
+
+impl Context {
+    /// Creates an empty context, where numbers are represented this way
+    pub fn with_arithmetic(arithmetic: Arithmetic) -> Context {
+        Context { arithmetic, ..Context::default() }
+    }
+
+    pub fn arithmetic(&self) -> Arithmetic {
+        self.arithmetic
+    }
+
+    /// Changes representation of numbers for the next calculations, saved values stay as they are
+    pub fn set_arithmetic(&mut self, arithmetic: Arithmetic) {
+        self.arithmetic = arithmetic;
+    }
+
+    pub fn format(&self) -> Format {
+        self.format
+    }
+
+    /// Changes how results are shown
+    pub fn set_format(&mut self, format: Format) {
+        self.format = format;
+    }
+
+    pub fn is_real_only(&self) -> bool {
+        self.real_only
+    }
+
+    /// Chooses, if real operations without a real result, like sqrt(-1), give complex numbers
+    pub fn set_real_only(&mut self, real_only: bool) {
+        self.real_only = real_only;
+    }
+
+    pub fn is_ieee(&self) -> bool {
+        self.ieee
+    }
+
+    /// Chooses, if operations like 1/0 give inf and NaN or errors
+    pub fn set_ieee(&mut self, ieee: bool) {
+        self.ieee = ieee;
+    }
+
+    pub fn is_strict(&self) -> bool {
+        self.strict
+    }
+
+    /// Chooses, if multiplication can be omitted, like 2x
+    pub fn set_strict(&mut self, strict: bool) {
+        self.strict = strict;
+    }
+
+    pub fn locale(&self) -> Locale {
+        self.locale
+    }
+
+    /// Changes how numbers are read from the next expressions
+    pub fn set_locale(&mut self, locale: Locale) {
+        self.locale = locale;
+    }
+
+    pub fn limits(&self) -> Limits {
+        self.limits
+    }
+
+    /// Changes bounds of the next calculations
+    pub fn set_limits(&mut self, limits: Limits) {
+        self.limits = limits;
+    }
+
+    /// Starts counting operations and time of a new message
+    pub(super) fn reset_steps(&self) {
+        self.steps.set(0);
+        self.start.set(Some(Instant::now()));
+    }
+
+    /// Counts one more operation. False, if there are too many of them or the time is over.
+    pub(super) fn take_step(&self) -> bool {
+        self.steps.set(self.steps.get() + 1);
+        self.steps.get() <= self.limits.max_steps && self.start.get().is_none_or(|start| start.elapsed() <= self.limits.max_time)
+    }
+
+    pub fn get_variable(&self, name: &str) -> Option<&Number> {
+        self.variables.get(name)
+    }
+
+    pub fn set_variable(&mut self, name: &str, value: Number) {
+        self.variables.insert(String::from(name), value);
+    }
+
+    /// Variables sorted by name
+    pub fn variables(&self) -> impl Iterator<Item = (&str, &Number)> {
+        self.variables.iter().map(|(name, value)| (name.as_str(), value))
+    }
+
+    pub(super) fn get_function(&self, name: &str) -> Option<&UserFunction> {
+        self.functions.get(name)
+    }
+
+    pub(super) fn set_function(&mut self, name: &str, function: UserFunction) {
+        self.functions.insert(String::from(name), function);
+    }
+
+    /// Definitions of functions sorted by name
+    pub fn functions(&self) -> impl Iterator<Item = &str> {
+        self.functions.values().map(|function| function.text.as_str())
+    }
+
+    /// Removes variables, functions and results, the next result is $1 again
+    pub fn clear(&mut self) {
+        self.variables.clear();
+        self.functions.clear();
+        self.results.clear();
+        self.forgotten_results = 0;
+    }
+
+    /// Remembers a result, so the next expressions can refer to it. Only the last `MAX_RESULTS` results are remembered.
+    pub fn push_result(&mut self, value: Number) {
+        if self.results.len() == MAX_RESULTS {
+            self.results.pop_front();
+            self.forgotten_results += 1;
+        }
+        self.results.push_back(value);
+    }
+
+    /// The last remembered result, it is `ans` or `_` in an expression
+    pub fn get_last_result(&self) -> Option<&Number> {
+        self.results.back()
+    }
+
+    /// The result by its number starting from 1, it is `$number` in an expression. Forgotten results are None.
+    pub fn get_result(&self, number: usize) -> Option<&Number> {
+        number.checked_sub(self.forgotten_results + 1).and_then(|i| self.results.get(i))
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn set_and_get_variable() {
+        use super::*;
+
+        let mut context = Context::default();
+        assert_eq!(context.get_variable("x"), None);
+
+        context.set_variable("x", Number::from(3.5));
+        context.set_variable("x", Number::from(4.5));
+        assert_eq!(context.get_variable("x"), Some(&Number::from(4.5)));
+    }
+
+    #[test]
+    fn variables_are_sorted() {
+        use super::*;
+
+        let mut context = Context::default();
+        context.set_variable("y", Number::from(2.0));
+        context.set_variable("x", Number::from(1.0));
+        assert_eq!(context.variables().collect::<Vec<(&str, &Number)>>(), vec![("x", &Number::from(1.0)), ("y", &Number::from(2.0))]);
+
+        context.clear();
+        assert_eq!(context.variables().count(), 0);
+    }
+
+    #[test]
+    fn set_and_get_function() {
+        use super::*;
+        use super::super::lexer::tokenize;
+        use super::super::locale::Locale;
+        use super::super::parser::parse;
+
+        let mut context = Context::default();
+        assert_eq!(context.get_function("f"), None);
+
+        let body = parse(&tokenize("x^2", Locale::default()).unwrap(), &Context::default()).unwrap();
+        context.set_function("g", UserFunction { parameters: vec![String::from("x")], body: body.clone(), text: String::from("g(x) = x^2") });
+        context.set_function("f", UserFunction { parameters: vec![String::from("x")], body, text: String::from("f(x) = x^2") });
+        assert_eq!(context.get_function("f").map(|function| function.parameters.len()), Some(1));
+        assert_eq!(context.functions().collect::<Vec<&str>>(), vec!["f(x) = x^2", "g(x) = x^2"]);
+
+        context.clear();
+        assert_eq!(context.functions().count(), 0);
+    }
+
+    #[test]
+    fn arithmetic() {
+        use super::*;
+
+        let mut context = Context::with_arithmetic(Arithmetic::Decimal(20));
+        assert_eq!(context.arithmetic(), Arithmetic::Decimal(20));
+
+        context.set_arithmetic(Arithmetic::Float);
+        assert_eq!(context.arithmetic(), Arithmetic::Float);
+        assert_eq!(Context::default().arithmetic(), Arithmetic::Float);
+    }
+
+    #[test]
+    fn format() {
+        use super::*;
+
+        let mut context = Context::default();
+        assert_eq!(context.format(), Format::default());
+
+        context.set_format(Format { mixed_fractions: true, ..Format::default() });
+        assert!(context.format().mixed_fractions);
+    }
+
+    #[test]
+    fn steps() {
+        use super::*;
+
+        let mut context = Context::default();
+        context.set_limits(Limits { max_steps: 2, ..Limits::default() });
+        assert!(context.take_step());
+        assert!(context.take_step());
+        assert!(!context.take_step());
+
+        context.reset_steps();
+        assert!(context.take_step());
+
+        context.set_limits(Limits { max_time: std::time::Duration::from_millis(1), ..Limits::default() });
+        context.reset_steps();
+        assert!(context.take_step());
+        std::thread::sleep(std::time::Duration::from_millis(2));
+        assert!(!context.take_step());
+    }
+
+    #[test]
+    fn results() {
+        use super::*;
+
+        let mut context = Context::default();
+        assert_eq!(context.get_last_result(), None);
+        assert_eq!(context.get_result(1), None);
+
+        context.push_result(Number::from(8.0));
+        context.push_result(Number::from(2.0));
+        assert_eq!(context.get_last_result(), Some(&Number::from(2.0)));
+        assert_eq!(context.get_result(0), None);
+        assert_eq!(context.get_result(1), Some(&Number::from(8.0)));
+        assert_eq!(context.get_result(2), Some(&Number::from(2.0)));
+        assert_eq!(context.get_result(3), None);
+
+        for i in 3..=MAX_RESULTS + 5 {
+            context.push_result(Number::from(i as i64));
+        }
+        assert_eq!(context.get_result(5), None);
+        assert_eq!(context.get_result(6), Some(&Number::from(6)));
+        assert_eq!(context.get_result(MAX_RESULTS + 5), Some(&Number::from((MAX_RESULTS + 5) as i64)));
+
+        context.clear();
+        assert_eq!(context.get_last_result(), None);
+        context.push_result(Number::from(1));
+        assert_eq!(context.get_result(1), Some(&Number::from(1)));
+    }
+}
@@ -0,0 +1,365 @@
+use std::num::NonZeroU64;
+use bigdecimal::{BigDecimal, RoundingMode};
+use num_bigint::BigInt;
+use num_rational::BigRational;
+use num_traits::{One, Signed, Zero};
+use super::number::{complex_to_string, Arithmetic, Number, DEFAULT_PRECISION};
+
+/// Significant digits of the decimal value, which is shown next to a fraction
+const FRACTION_DECIMAL_DIGITS: u64 = 16;
+/// Digits after the point of a number in another base, if the count of decimals isn't chosen
+const BASE_FRACTION_DIGITS: u64 = 16;
+
+/// How many digits of a number are shown. Numbers are rounded half away from zero.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Digits {
+    /// All digits of the value
+    #[default]
+    All,
+    /// Digits after the decimal point: 3.14 for 2
+    Decimals(u64),
+    /// Significant digits: 3.142 for 4
+    Significant(u64)
+}
+
+/// How the magnitude of a number is shown
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Notation {
+    /// 12345.6
+    #[default]
+    Plain,
+    /// One digit before the decimal point: 1.23456e4
+    Scientific,
+    /// The exponent is a multiple of 3: 12.3456e3
+    Engineering
+}
+
+/// Numbers are written in another base: 0xFF for 255 in base 16
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Base {
+    /// From 2 to 36, digits after 9 are letters
+    pub radix: u32,
+    /// Integers are written with this count of bits, negative ones in two's complement: 0xFF for -1 in 8 bits
+    pub width: Option<u32>
+}
+
+/// How results are shown to the user
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Format {
+    /// Fractions are shown as mixed numbers: 1 1/2 instead of 3/2
+    pub mixed_fractions: bool,
+    /// Fractions are followed by their decimal value: 1/3 ≈ 0.3333333333333333
+    pub fraction_decimal: bool,
+    /// Digits of numbers, which aren't fractions
+    pub digits: Digits,
+    pub notation: Notation,
+    /// Separates groups of three digits in the integer part: 1 234 567
+    pub group_separator: Option<char>,
+    /// Zeros at the end of the fractional part are dropped: 0.5 instead of 0.500
+    pub trim_zeros: bool,
+    /// Real numbers, fractions too, are written in this base instead of the decimal one
+    pub base: Option<Base>
+}
+
+impl Format {
+    /// Fractions are shown as they are, other numbers are rounded and written in the notation.
+    /// Infinity and NaN are always the same.
+    pub fn render(&self, number: &Number) -> String {
+        if let Some(text) = self.base.and_then(|base| self.render_in_base(number, base)) { return text }
+
+        let fraction = match number {
+            Number::Rational(fraction) => fraction,
+            Number::Complex(n) => return complex_to_string(*n, |part| self.render_real(&Number::Float(part))),
+            _ => return self.render_real(number)
+        };
+        let (numer, denom) = (fraction.numer(), fraction.denom());
+
+        let mut text = if self.mixed_fractions && numer.abs() > *denom {
+            // Division of integers truncates, so the whole part has the sign and the rest is positive
+            format!("{} {}/{}", numer / denom, (numer % denom).abs(), denom)
+        }
+        else {
+            number.to_string()
+        };
+
+        if self.fraction_decimal {
+            let (decimal, is_exact) = to_decimal(numer, denom);
+            text = format!("{} {} {}", text, if is_exact { "=" } else { "≈" }, decimal.to_plain_string());
+        }
+
+        text
+    }
+}
+
+impl Format {
+    fn render_real(&self, number: &Number) -> String {
+        let is_default = self.digits == Digits::All && self.notation == Notation::Plain && self.group_separator.is_none();
+        let value = match number {
+            _ if is_default => return number.to_string(),
+            Number::Float(n) => Number::float_to_exact(*n, Arithmetic::Decimal(DEFAULT_PRECISION)).and_then(|n| n.to_decimal()),
+            _ => number.to_decimal()
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return number.to_string()
+        };
+
+        let (mantissa, exponent) = match self.notation {
+            Notation::Plain => {
+                let rounded = match self.digits {
+                    Digits::All => value.normalized(),
+                    Digits::Decimals(decimals) => value.with_scale_round(decimals as i64, RoundingMode::HalfUp),
+                    Digits::Significant(digits) => round_significant(&value, digits)
+                };
+                (rounded, None)
+            },
+            Notation::Scientific | Notation::Engineering => {
+                let magnitude = value.order_of_magnitude();
+                let step = if self.notation == Notation::Engineering { 3 } else { 1 };
+                // Digits before the decimal point take part in the precision
+                let rounded = match self.digits {
+                    Digits::All => value.normalized(),
+                    Digits::Decimals(decimals) => round_significant(&value, decimals + 1 + magnitude.rem_euclid(step) as u64),
+                    Digits::Significant(digits) => round_significant(&value, digits)
+                };
+
+                // Rounding can make one more digit: 9.99 → 10.0
+                let exponent = if rounded.is_zero() { 0 } else { rounded.order_of_magnitude().div_euclid(step) * step };
+                let (digits, scale) = rounded.as_bigint_and_exponent();
+                let mut mantissa = BigDecimal::new(digits, scale + exponent);
+                if let Digits::Decimals(decimals) = self.digits {
+                    mantissa = mantissa.with_scale_round(decimals as i64, RoundingMode::HalfUp);
+                }
+                (mantissa, Some(exponent))
+            }
+        };
+
+        let mut text = mantissa.to_plain_string();
+        if self.trim_zeros && text.contains('.') {
+            text = String::from(text.trim_end_matches('0').trim_end_matches('.'));
+        }
+        if let Some(separator) = self.group_separator {
+            text = group_digits(&text, separator, 3);
+        }
+
+        match exponent {
+            Some(exponent) => format!("{}e{}", text, exponent),
+            None => text
+        }
+    }
+}
+
+impl Format {
+    /// Digits after the point are the chosen decimals, the notation isn't used.
+    /// Hexadecimal, octal and binary numbers have prefixes like in expressions: 0xFF, 0o17, 0b101.
+    /// Complex numbers, infinity and NaN aren't written in other bases.
+    /// Numbers too big or too small to be converted exactly are written in the decimal scientific notation.
+    fn render_in_base(&self, number: &Number, base: Base) -> Option<String> {
+        let value = match number {
+            Number::Float(n) if n.is_finite() => Number::float_to_exact(*n, Arithmetic::Rational)?.to_limited_rational(),
+            Number::Float(_) | Number::Complex(_) => return None,
+            _ => number.to_limited_rational()
+        };
+        let value = match value {
+            Some(value) => value,
+            None => return Some(Format { notation: Notation::Scientific, base: None, ..*self }.render(number))
+        };
+        let radix = BigInt::from(base.radix);
+        let fraction_digits = match self.digits {
+            Digits::Decimals(decimals) => decimals,
+            Digits::All | Digits::Significant(_) => BASE_FRACTION_DIGITS
+        };
+
+        // Digits of the absolute value without the point, rounded half up
+        let scaled = value.abs() * BigRational::from_integer(radix.pow(fraction_digits as u32));
+        let rounded: BigInt = (scaled.numer() * 2 + scaled.denom()) / (scaled.denom() * 2);
+        let mut digits = rounded.to_str_radix(base.radix).to_uppercase();
+        let mut is_negative = value.is_negative();
+
+        if let (Some(width), true) = (base.width, value.is_integer()) {
+            let modulus = BigInt::one() << width;
+            let min = -(modulus.clone() >> 1u32);
+            let integer = value.to_integer();
+            // Negative integers, which fit the width, are written in two's complement
+            let integer = if is_negative && integer >= min {
+                is_negative = false;
+                &modulus + integer
+            }
+            else {
+                integer.abs()
+            };
+            // All integers of the width have the same count of digits: 0x00FF in 16 bits
+            let width_digits = (modulus - BigInt::one()).to_str_radix(base.radix).len();
+            digits = format!("{:0>width$}{}", integer.to_str_radix(base.radix).to_uppercase(), "0".repeat(fraction_digits as usize), width = width_digits);
+        }
+
+        let fraction_digits = fraction_digits as usize;
+        if fraction_digits > 0 {
+            digits = format!("{:0>width$}", digits, width = fraction_digits + 1);
+            digits.insert(digits.len() - fraction_digits, '.');
+            if self.trim_zeros || !matches!(self.digits, Digits::Decimals(_)) {
+                digits = String::from(digits.trim_end_matches('0').trim_end_matches('.'));
+            }
+        }
+
+        if let Some(separator) = self.group_separator {
+            // Bits are grouped by nibbles
+            let size = if matches!(base.radix, 2 | 16) { 4 } else { 3 };
+            digits = group_digits(&digits, separator, size);
+        }
+
+        let sign = if is_negative { "-" } else { "" };
+        Some(match base.radix {
+            16 => format!("{}0x{}", sign, digits),
+            8 => format!("{}0o{}", sign, digits),
+            2 => format!("{}0b{}", sign, digits),
+            10 => format!("{}{}", sign, digits),
+            radix => format!("{}{} (base {})", sign, digits, radix)
+        })
+    }
+}
+
+/// Pads with zeros, if there are less digits: 0.5000 for 4
+fn round_significant(value: &BigDecimal, digits: u64) -> BigDecimal {
+    match NonZeroU64::new(digits) {
+        Some(digits) => value.with_precision_round(digits, RoundingMode::HalfUp),
+        None => value.normalized()
+    }
+}
+
+/// Inserts the separator between groups of digits of the integer part
+fn group_digits(number: &str, separator: char, size: usize) -> String {
+    let (sign, unsigned) = number.strip_prefix('-').map_or(("", number), |unsigned| ("-", unsigned));
+    let integer_length = unsigned.find('.').unwrap_or(unsigned.len());
+
+    let mut grouped = String::from(sign);
+    for (i, digit) in unsigned[..integer_length].chars().enumerate() {
+        if i > 0 && (integer_length - i) % size == 0 { grouped.push(separator); }
+        grouped.push(digit);
+    }
+    grouped.push_str(&unsigned[integer_length..]);
+    grouped
+}
+
+/// Divides with the limited precision and tells, if the result is exact
+fn to_decimal(numer: &BigInt, denom: &BigInt) -> (BigDecimal, bool) {
+    let decimal = (BigDecimal::from(numer.clone()) / BigDecimal::from(denom.clone())).with_prec(FRACTION_DECIMAL_DIGITS).normalized();
+    let is_exact = &decimal * BigDecimal::from(denom.clone()) == BigDecimal::from(numer.clone());
+
+    (decimal, is_exact)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn render_not_fractions() {
+        use super::*;
+
+        let format = Format { mixed_fractions: true, fraction_decimal: true, ..Format::default() };
+        assert_eq!(format.render(&Number::from(3)), "3");
+        assert_eq!(format.render(&Number::from(0.5)), "0.5");
+    }
+
+    #[test]
+    fn render_fractions() {
+        use super::*;
+
+        let three_halves = Number::from(3).div(&Number::from(2), Arithmetic::Rational);
+        let minus_third = Number::from(-1).div(&Number::from(3), Arithmetic::Rational);
+
+        assert_eq!(Format::default().render(&three_halves), "3/2");
+        assert_eq!(Format::default().render(&minus_third), "-1/3");
+
+        let mixed = Format { mixed_fractions: true, ..Format::default() };
+        assert_eq!(mixed.render(&three_halves), "1 1/2");
+        assert_eq!(mixed.render(&three_halves.neg()), "-1 1/2");
+        assert_eq!(mixed.render(&minus_third), "-1/3");
+
+        let with_decimal = Format { fraction_decimal: true, ..Format::default() };
+        assert_eq!(with_decimal.render(&three_halves), "3/2 = 1.5");
+        assert_eq!(with_decimal.render(&minus_third), "-1/3 ≈ -0.3333333333333333");
+    }
+
+    #[test]
+    fn render_digits() {
+        use super::*;
+
+        let sum = Number::from(0.1).add(&Number::from(0.2), Arithmetic::Float);
+        let decimals = Format { digits: Digits::Decimals(2), ..Format::default() };
+        assert_eq!(decimals.render(&sum), "0.30");
+        assert_eq!(decimals.render(&Number::from(2.675)), "2.68");
+        assert_eq!(decimals.render(&Number::from(-0.001)), "0.00");
+        assert_eq!(Format { trim_zeros: true, ..decimals }.render(&sum), "0.3");
+
+        let significant = Format { digits: Digits::Significant(3), ..Format::default() };
+        assert_eq!(significant.render(&Number::from(0.5)), "0.500");
+        assert_eq!(significant.render(&Number::from(123456)), "123000");
+        assert_eq!(significant.render(&Number::parse("3.14159", Arithmetic::Decimal(50)).unwrap()), "3.14");
+        assert_eq!(significant.render(&Number::from(f64::INFINITY)), "inf");
+    }
+
+    #[test]
+    fn render_notations() {
+        use super::*;
+
+        let scientific = Format { notation: Notation::Scientific, ..Format::default() };
+        assert_eq!(scientific.render(&Number::from(1e21)), "1e21");
+        assert_eq!(scientific.render(&Number::from(-123456)), "-1.23456e5");
+        assert_eq!(scientific.render(&Number::from(0.00015)), "1.5e-4");
+        assert_eq!(scientific.render(&Number::from(0)), "0e0");
+        assert_eq!(Format { digits: Digits::Decimals(2), ..scientific }.render(&Number::from(9.999)), "1.00e1");
+
+        let engineering = Format { notation: Notation::Engineering, ..Format::default() };
+        assert_eq!(engineering.render(&Number::from(123456)), "123.456e3");
+        assert_eq!(engineering.render(&Number::from(0.00015)), "150e-6");
+        assert_eq!(Format { digits: Digits::Decimals(1), ..engineering }.render(&Number::from(999.96)), "1.0e3");
+        assert_eq!(Format { digits: Digits::Significant(2), ..engineering }.render(&Number::from(12345)), "12e3");
+    }
+
+    #[test]
+    fn render_groups() {
+        use super::*;
+
+        let grouped = Format { group_separator: Some(','), ..Format::default() };
+        assert_eq!(grouped.render(&Number::from(1234567)), "1,234,567");
+        assert_eq!(grouped.render(&Number::from(-123456.789)), "-123,456.789");
+        assert_eq!(grouped.render(&Number::from(999)), "999");
+        assert_eq!(Format { group_separator: Some(' '), ..Format::default() }.render(&Number::from(1e6)), "1 000 000");
+
+        let complex = Number::complex(num_complex::Complex64::new(1234.5, -0.25), Arithmetic::Float);
+        assert_eq!(Format { digits: Digits::Decimals(1), ..grouped }.render(&complex), "1,234.5 - 0.3i");
+    }
+
+    #[test]
+    fn render_in_base() {
+        use super::*;
+
+        let hex = Format { base: Some(Base { radix: 16, width: None }), ..Format::default() };
+        assert_eq!(hex.render(&Number::from(255)), "0xFF");
+        assert_eq!(hex.render(&Number::from(-255)), "-0xFF");
+        assert_eq!(hex.render(&Number::from(0.5)), "0x0.8");
+        assert_eq!(hex.render(&Number::from(1).div(&Number::from(3), Arithmetic::Rational)), "0x0.5555555555555555");
+        assert_eq!(Format { digits: Digits::Decimals(2), ..hex }.render(&Number::from(0.1)), "0x0.1A");
+        assert_eq!(hex.render(&Number::from(f64::NAN)), "NaN");
+
+        let binary = Format { base: Some(Base { radix: 2, width: None }), group_separator: Some('_'), ..Format::default() };
+        assert_eq!(binary.render(&Number::from(0b1011_0110)), "0b1011_0110");
+        let base36 = Format { base: Some(Base { radix: 36, width: None }), ..Format::default() };
+        assert_eq!(base36.render(&Number::from(71)), "1Z (base 36)");
+    }
+
+    #[test]
+    fn render_twos_complement() {
+        use super::*;
+
+        let byte = Format { base: Some(Base { radix: 16, width: Some(8) }), ..Format::default() };
+        assert_eq!(byte.render(&Number::from(-1)), "0xFF");
+        assert_eq!(byte.render(&Number::from(-128)), "0x80");
+        assert_eq!(byte.render(&Number::from(-129)), "-0x81");
+        assert_eq!(byte.render(&Number::from(5)), "0x05");
+        assert_eq!(byte.render(&Number::from(-0.5)), "-0x0.8");
+
+        let word = Format { base: Some(Base { radix: 2, width: Some(16) }), ..Format::default() };
+        assert_eq!(word.render(&Number::from(-2)), "0b1111111111111110");
+    }
+}
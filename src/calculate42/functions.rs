@@ -0,0 +1,231 @@
+use std::cmp::Ordering;
+use bigdecimal::RoundingMode;
+use num_complex::Complex64;
+use super::number::Number;
+
+/// Built-in mathematical functions
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub enum Function {
+    Sin,
+    Cos,
+    Tan,
+    Asin,
+    Acos,
+    Atan,
+    Sinh,
+    Cosh,
+    Tanh,
+    Asinh,
+    Acosh,
+    Atanh,
+    Sqrt,
+    Cbrt,
+    Exp,
+    Ln,
+    Log10,
+    Log,
+    Abs,
+    Floor,
+    Ceil,
+    Round,
+    Min,
+    Max
+}
+
+pub const FUNCTIONS: [Function; 24] = [
+    Function::Sin, Function::Cos, Function::Tan, Function::Asin, Function::Acos, Function::Atan,
+    Function::Sinh, Function::Cosh, Function::Tanh, Function::Asinh, Function::Acosh, Function::Atanh,
+    Function::Sqrt, Function::Cbrt, Function::Exp, Function::Ln, Function::Log10, Function::Log,
+    Function::Abs, Function::Floor, Function::Ceil, Function::Round, Function::Min, Function::Max
+];
+
+impl Function {
+    pub fn from_name(name: &str) -> Option<Function> {
+        FUNCTIONS.iter().find(|function| function.name() == name).copied()
+    }
+
+    pub fn name(&self) -> &'static str {
+        match self {
+            Function::Sin => "sin",
+            Function::Cos => "cos",
+            Function::Tan => "tan",
+            Function::Asin => "asin",
+            Function::Acos => "acos",
+            Function::Atan => "atan",
+            Function::Sinh => "sinh",
+            Function::Cosh => "cosh",
+            Function::Tanh => "tanh",
+            Function::Asinh => "asinh",
+            Function::Acosh => "acosh",
+            Function::Atanh => "atanh",
+            Function::Sqrt => "sqrt",
+            Function::Cbrt => "cbrt",
+            Function::Exp => "exp",
+            Function::Ln => "ln",
+            Function::Log10 => "log10",
+            Function::Log => "log",
+            Function::Abs => "abs",
+            Function::Floor => "floor",
+            Function::Ceil => "ceil",
+            Function::Round => "round",
+            Function::Min => "min",
+            Function::Max => "max"
+        }
+    }
+
+    /// Checks if the function can take this count of arguments
+    pub fn is_arguments_count_correct(&self, count: usize) -> bool {
+        match self {
+            Function::Log => count == 2,
+            Function::Min | Function::Max => count >= 1,
+            _ => count == 1
+        }
+    }
+
+    /// Calculates the function. The count of arguments must be checked before.
+    pub fn apply(&self, args: &[f64]) -> f64 {
+        match self {
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Asin => args[0].asin(),
+            Function::Acos => args[0].acos(),
+            Function::Atan => args[0].atan(),
+            Function::Sinh => args[0].sinh(),
+            Function::Cosh => args[0].cosh(),
+            Function::Tanh => args[0].tanh(),
+            Function::Asinh => args[0].asinh(),
+            Function::Acosh => args[0].acosh(),
+            Function::Atanh => args[0].atanh(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Cbrt => args[0].cbrt(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            // Powers of 0 are only 0 and 1, so log(0, x) isn't defined, not 0
+            Function::Log if args[0] <= 0.0 => f64::NAN,
+            Function::Log => args[1].log(args[0]),
+            Function::Abs => args[0].abs(),
+            Function::Floor => args[0].floor(),
+            Function::Ceil => args[0].ceil(),
+            Function::Round => args[0].round(),
+            Function::Min => args.iter().copied().fold(f64::INFINITY, f64::min),
+            Function::Max => args.iter().copied().fold(f64::NEG_INFINITY, f64::max)
+        }
+    }
+
+    /// Calculates the function without floats, if the result of the function is exact for these arguments.
+    /// The count of arguments must be checked before.
+    pub fn apply_exact(&self, args: &[Number]) -> Option<Number> {
+        if args.iter().any(|arg| matches!(arg, Number::Float(_) | Number::Complex(_))) { return None }
+
+        match self {
+            Function::Abs => Some(args[0].abs()),
+            Function::Floor => args[0].to_integer(RoundingMode::Floor),
+            Function::Ceil => args[0].to_integer(RoundingMode::Ceiling),
+            Function::Round => args[0].to_integer(RoundingMode::HalfUp),
+            Function::Min => args.iter().min_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal)).cloned(),
+            Function::Max => args.iter().max_by(|a, b| a.partial_cmp(b).unwrap_or(Ordering::Equal)).cloned(),
+            _ => None
+        }
+    }
+
+    /// Calculates the function with complex numbers, if it is defined for them.
+    /// The count of arguments must be checked before.
+    pub fn apply_complex(&self, args: &[Complex64]) -> Option<Complex64> {
+        let value = match self {
+            Function::Sin => args[0].sin(),
+            Function::Cos => args[0].cos(),
+            Function::Tan => args[0].tan(),
+            Function::Asin => args[0].asin(),
+            Function::Acos => args[0].acos(),
+            Function::Atan => args[0].atan(),
+            Function::Sinh => args[0].sinh(),
+            Function::Cosh => args[0].cosh(),
+            Function::Tanh => args[0].tanh(),
+            Function::Asinh => args[0].asinh(),
+            Function::Acosh => args[0].acosh(),
+            Function::Atanh => args[0].atanh(),
+            Function::Sqrt => args[0].sqrt(),
+            Function::Cbrt => args[0].cbrt(),
+            Function::Exp => args[0].exp(),
+            Function::Ln => args[0].ln(),
+            Function::Log10 => args[0].log10(),
+            Function::Log if args[0] == Complex64::new(0.0, 0.0) => return None,
+            Function::Log => args[1].ln() / args[0].ln(),
+            Function::Abs => Complex64::from(args[0].norm()),
+            // Complex numbers aren't ordered
+            Function::Floor | Function::Ceil | Function::Round | Function::Min | Function::Max => return None
+        };
+        Some(value)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn from_name_all_functions() {
+        use super::*;
+
+        for function in FUNCTIONS {
+            assert_eq!(Function::from_name(function.name()), Some(function));
+        }
+        assert_eq!(Function::from_name("sinus"), None);
+        assert_eq!(Function::from_name("Sin"), None);
+    }
+
+    #[test]
+    fn arguments_count() {
+        use super::*;
+
+        assert!(Function::Sqrt.is_arguments_count_correct(1));
+        assert!(!Function::Sqrt.is_arguments_count_correct(0));
+        assert!(!Function::Sqrt.is_arguments_count_correct(2));
+        assert!(Function::Log.is_arguments_count_correct(2));
+        assert!(!Function::Log.is_arguments_count_correct(1));
+        assert!(Function::Max.is_arguments_count_correct(1));
+        assert!(Function::Max.is_arguments_count_correct(5));
+        assert!(!Function::Max.is_arguments_count_correct(0));
+    }
+
+    #[test]
+    fn apply_correct() {
+        use super::*;
+
+        assert_eq!(Function::Sqrt.apply(&[16.0]), 4.0);
+        assert_eq!(Function::Cbrt.apply(&[-27.0]), -3.0);
+        assert_eq!(Function::Log.apply(&[2.0, 1024.0]), 10.0);
+        assert_eq!(Function::Log10.apply(&[1000.0]), 3.0);
+        assert_eq!(Function::Abs.apply(&[-2.5]), 2.5);
+        assert_eq!(Function::Round.apply(&[2.5]), 3.0);
+        assert_eq!(Function::Floor.apply(&[-2.5]), -3.0);
+        assert_eq!(Function::Ceil.apply(&[-2.5]), -2.0);
+        assert_eq!(Function::Min.apply(&[3.0, -1.0, 2.0]), -1.0);
+        assert_eq!(Function::Max.apply(&[3.0, -1.0, 2.0]), 3.0);
+        assert_eq!(Function::Sin.apply(&[0.0]), 0.0);
+        assert_eq!(Function::Cosh.apply(&[0.0]), 1.0);
+    }
+
+    #[test]
+    fn apply_exact_correct() {
+        use super::*;
+
+        let big = Number::parse("123456789012345678901234567890", super::super::number::Arithmetic::Float).unwrap();
+        assert_eq!(Function::Abs.apply_exact(&[big.neg()]).map(|n| n.to_string()), Some(big.to_string()));
+        assert_eq!(Function::Max.apply_exact(&[Number::from(3), big.clone(), Number::from(-1)]), Some(big));
+        assert_eq!(Function::Min.apply_exact(&[Number::from(3), Number::from(-1)]), Some(Number::from(-1)));
+        assert_eq!(Function::Round.apply_exact(&[Number::from(7)]), Some(Number::from(7)));
+        assert_eq!(Function::Sqrt.apply_exact(&[Number::from(16)]), None);
+        assert_eq!(Function::Abs.apply_exact(&[Number::from(-2.5)]), None);
+    }
+
+    #[test]
+    fn apply_complex_correct() {
+        use super::*;
+
+        assert_eq!(Function::Sqrt.apply_complex(&[Complex64::new(-4.0, 0.0)]), Some(Complex64::new(0.0, 2.0)));
+        assert_eq!(Function::Abs.apply_complex(&[Complex64::new(3.0, -4.0)]), Some(Complex64::new(5.0, 0.0)));
+        assert_eq!(Function::Exp.apply_complex(&[Complex64::new(0.0, 0.0)]), Some(Complex64::new(1.0, 0.0)));
+        assert_eq!(Function::Max.apply_complex(&[Complex64::i(), Complex64::new(1.0, 0.0)]), None);
+    }
+}
@@ -0,0 +1,453 @@
+use std::iter::Peekable;
+use std::str::CharIndices;
+use num_bigint::BigInt;
+use super::locale::Locale;
+use super::{CalcError, CalcErrorType};
+
+/// Position of a token in the input, in bytes. The end is exclusive.
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Span {
+    pub start: usize,
+    pub end: usize
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum TokenKind {
+    /// Text of a decimal number with a dot as the decimal separator, maybe with an exponent: 6.02e23
+    Number(String),
+    /// Number with the imaginary unit right after it: 4i
+    Imaginary(String),
+    Identifier(String),
+    Result(usize),
+    Comma,
+    Plus,
+    Minus,
+    Star,
+    Slash,
+    DoubleSlash,
+    Percent,
+    Caret,
+    Bang,
+    Ampersand,
+    Pipe,
+    Tilde,
+    ShiftLeft,
+    ShiftRight,
+    Equals,
+    LeftBracket,
+    RightBracket
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub struct Token {
+    pub kind: TokenKind,
+    pub span: Span
+}
+
+/// Splits a string into tokens. Whitespaces separate tokens and are skipped, unless they group digits of a number.
+/// A comma, which isn't a part of a number in the locale, separates arguments of a function call.
+pub fn tokenize(math_expr: &str, locale: Locale) -> Result<Vec<Token>, CalcError> {
+    let mut tokens: Vec<Token> = Vec::new();
+    let mut chars = math_expr.char_indices().peekable();
+    // For every opened bracket: is it a bracket of a function call
+    let mut brackets: Vec<bool> = Vec::new();
+
+    while let Some((start, current_ch)) = chars.next() {
+        let mut end = start + current_ch.len_utf8();
+        let is_in_call = brackets.last() == Some(&true);
+
+        let kind = match current_ch {
+            whitespace if whitespace.is_whitespace() => continue,
+            '+' => TokenKind::Plus,
+            '-' => TokenKind::Minus,
+            '*' => TokenKind::Star,
+            '/' if chars.next_if(|&(_, ch)| ch == '/').is_some() => {
+                end += 1;
+                TokenKind::DoubleSlash
+            },
+            '/' => TokenKind::Slash,
+            '&' => TokenKind::Ampersand,
+            '|' => TokenKind::Pipe,
+            '~' => TokenKind::Tilde,
+            '<' | '>' if chars.next_if(|&(_, ch)| ch == current_ch).is_some() => {
+                end += 1;
+                if current_ch == '<' { TokenKind::ShiftLeft } else { TokenKind::ShiftRight }
+            },
+            '%' => TokenKind::Percent,
+            '^' => TokenKind::Caret,
+            '!' => TokenKind::Bang,
+            '=' => TokenKind::Equals,
+            '(' => {
+                brackets.push(matches!(tokens.last(), Some(Token { kind: TokenKind::Identifier(_), .. })));
+                TokenKind::LeftBracket
+            },
+            ')' => {
+                brackets.pop();
+                TokenKind::RightBracket
+            },
+            '$' => {
+                // Reference to a previous result by its number
+                end = skip_while(&mut chars, end, |ch| ch.is_ascii_digit());
+
+                match math_expr[start + 1..end].parse() {
+                    Ok(number) => TokenKind::Result(number),
+                    Err(_) => return Err(CalcError::with_span(CalcErrorType::OperandNotNumber, Span { start, end }))
+                }
+            },
+            letter if is_identifier_start(letter) => {
+                end = skip_while(&mut chars, end, is_identifier_char);
+
+                TokenKind::Identifier(String::from(&math_expr[start..end]))
+            },
+            _ if decimal_length(&math_expr[start..], locale, is_in_call) > 0 => {
+                let radix = match (current_ch, chars.peek().map(|&(_, ch)| ch.to_ascii_lowercase())) {
+                    ('0', Some('x')) => 16,
+                    ('0', Some('o')) => 8,
+                    ('0', Some('b')) => 2,
+                    _ => 10
+                };
+
+                // A number lasts until the first char that can't be in a number.
+                // Letters after a prefix are taken too, so 0xFG is a wrong number and not 0xF * G.
+                if radix == 10 {
+                    end = start + decimal_length(&math_expr[start..], locale, is_in_call);
+                    end += exponent_length(&math_expr[end..]);
+                    while chars.next_if(|&(i, _)| i < end).is_some() {}
+                }
+                else {
+                    end = skip_while(&mut chars, end, |ch| is_identifier_char(ch) || ch == '.');
+                }
+
+                let literal = match try_parse_operand(&locale.normalize(&math_expr[start..end]), radix) {
+                    Some(literal) => literal,
+                    None => return Err(CalcError::with_span(CalcErrorType::OperandNotNumber, Span { start, end }))
+                };
+
+                // `i` is a part of the number, if it isn't the start of a name like `in`
+                let rest = &math_expr[end..];
+                if rest.starts_with('i') && !rest[1..].starts_with(is_identifier_char) {
+                    chars.next();
+                    end += 1;
+                    TokenKind::Imaginary(literal)
+                }
+                else {
+                    TokenKind::Number(literal)
+                }
+            },
+            ',' => TokenKind::Comma,
+            _ => return Err(CalcError::with_span(CalcErrorType::NotMathExpr, Span { start, end }))
+        };
+
+        tokens.push(Token { kind, span: Span { start, end } });
+    }
+
+    Ok(tokens)
+}
+
+/// Takes chars while they match, returns the end of the last taken one
+fn skip_while(chars: &mut Peekable<CharIndices>, mut end: usize, predicate: impl Fn(char) -> bool) -> usize {
+    while let Some((i, ch)) = chars.next_if(|&(_, ch)| predicate(ch)) {
+        end = i + ch.len_utf8();
+    }
+    end
+}
+
+/// Length of a decimal number without an exponent at the start of the text. `_` separates digits: 1_000_000.
+/// A group separator is a part of the number between a digit and three digits: 1 234 567.
+/// A comma is a part of the number, if it is a decimal separator. Then it needs a digit after it,
+/// so `max(1,5, 2)` has two arguments, or without the decimal separator in the locale it can't be in a function call.
+fn decimal_length(text: &str, locale: Locale, is_in_call: bool) -> usize {
+    let mut length = 0;
+    for (i, ch) in text.char_indices() {
+        let rest = &text[i + ch.len_utf8()..];
+        let is_part = match ch {
+            '0'..='9' | '_' => true,
+            _ if locale.is_group_separator(ch) => {
+                text[..i].ends_with(|ch: char| ch.is_ascii_digit())
+                    && rest.len() >= 3 && rest.bytes().take(3).all(|b| b.is_ascii_digit())
+                    && !rest[3..].starts_with(|ch: char| ch.is_ascii_digit() || ch == '_')
+            },
+            ',' if locale.decimal_separator.is_none() => !is_in_call,
+            ',' if locale.is_decimal_separator(',') => rest.starts_with(|ch: char| ch.is_ascii_digit()),
+            _ => locale.is_decimal_separator(ch)
+        };
+
+        if !is_part { break }
+        length = i + ch.len_utf8();
+    }
+    length
+}
+
+/// Length of the exponent at the start of the text: e23, E-9. Without digits `e` is the constant: 2e = 2 * e.
+fn exponent_length(text: &str) -> usize {
+    let rest = match text.strip_prefix(['e', 'E']) {
+        Some(rest) => rest,
+        None => return 0
+    };
+    let digits = rest.strip_prefix(['+', '-']).unwrap_or(rest);
+    if !digits.starts_with(|ch: char| ch.is_ascii_digit()) { return 0 }
+
+    let length = digits.find(|ch: char| !ch.is_ascii_digit() && ch != '_').unwrap_or(digits.len());
+    text.len() - digits.len() + length
+}
+
+fn is_identifier_start(ch: char) -> bool {
+    ch.is_alphabetic() || ch == '_'
+}
+
+fn is_identifier_char(ch: char) -> bool {
+    ch.is_alphanumeric() || ch == '_'
+}
+
+/// Checks if text is a number of any length with a dot as the decimal separator, `_` can stand between digits.
+/// Numbers with a prefix (0x, 0o, 0b) are integers, they are written in decimal digits.
+/// The number is converted later, when it is known, which arithmetic is used.
+fn try_parse_operand(operand: &str, radix: u32) -> Option<String> {
+    let is_separated = operand.match_indices('_').all(|(i, _)| {
+        operand[..i].ends_with(|ch: char| ch.is_digit(radix)) && operand[i + 1..].starts_with(|ch: char| ch.is_digit(radix))
+    });
+    if !is_separated { return None }
+    let operand = operand.replace('_', "");
+
+    if radix != 10 {
+        return BigInt::parse_bytes(&operand.as_bytes()[2..], radix).map(|n| n.to_string())
+    }
+
+    let literal = operand.replace('E', "e");
+    literal.parse::<f64>().ok().map(|_| literal)
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn tokenize_looks_like_math_expr_without_whitespaces() {
+        use super::*;
+
+        for message in ["2.0+2,0", "3*3", "4/4", "5-5", "1**1", "6//6", "7%7", ")8(8", "9^9"] {
+            assert!(tokenize(message, Locale::default()).is_ok());
+        }
+    }
+
+    #[test]
+    fn tokenize_looks_like_math_expr_with_whitespaces() {
+        use super::*;
+
+        for message in ["2.0 + 2, 2", "3 * 3", "4 /4", "5- 5", "1* *1", "6    //6", "7%   7", ") 8(    8", "9^ 9"] {
+            assert!(tokenize(message, Locale::default()).is_ok());
+        }
+    }
+
+    #[test]
+    fn tokenize_definitely_not_math_expr() {
+        use super::*;
+
+        for message in ["4 @/4", "5- ?5", "2 < 3", "7 # 7", "\"word\"", "1 <> 2"] {
+            assert_eq!(tokenize(message, Locale::default()).map_err(|e| e.error_type()), Err(CalcErrorType::NotMathExpr));
+        }
+    }
+
+    #[test]
+    fn tokenize_not_math_expr_span() {
+        use super::*;
+
+        assert_eq!(tokenize("2 + 2?", Locale::default()).map_err(|e| e.span()), Err(Some(Span { start: 5, end: 6 })));
+    }
+
+    #[test]
+    fn tokenize_empty() {
+        use super::*;
+
+        assert_eq!(tokenize("", Locale::default()), Ok(Vec::new()));
+        assert_eq!(tokenize("   ", Locale::default()), Ok(Vec::new()));
+    }
+
+    #[test]
+    fn tokenize_spans() {
+        use super::*;
+
+        let tokens = tokenize("12.5 *(3)", Locale::default()).unwrap();
+        let spans: Vec<(usize, usize)> = tokens.iter().map(|t| (t.span.start, t.span.end)).collect();
+        assert_eq!(spans, vec![(0, 4), (5, 6), (6, 7), (7, 8), (8, 9)]);
+    }
+
+    #[test]
+    fn tokenize_numbers_are_not_merged() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("2 3 87", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![TokenKind::Number(String::from("2")), TokenKind::Number(String::from("3")), TokenKind::Number(String::from("87"))]);
+    }
+
+    #[test]
+    fn tokenize_number_with_comma() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("2387,2", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![TokenKind::Number(String::from("2387.2"))]);
+    }
+
+    #[test]
+    fn tokenize_long_number() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("123456789012345678901234567890", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![TokenKind::Number(String::from("123456789012345678901234567890"))]);
+    }
+
+    #[test]
+    fn tokenize_imaginary_number() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("2.5i+i 4in", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Imaginary(String::from("2.5")),
+            TokenKind::Plus,
+            TokenKind::Identifier(String::from("i")),
+            TokenKind::Number(String::from("4")),
+            TokenKind::Identifier(String::from("in"))
+        ]);
+    }
+
+    #[test]
+    fn tokenize_scientific_notation() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("6,02E23 1e-9i 2e 3e+x 1_000.5e1_0", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Number(String::from("6.02e23")),
+            TokenKind::Imaginary(String::from("1e-9")),
+            TokenKind::Number(String::from("2")),
+            TokenKind::Identifier(String::from("e")),
+            TokenKind::Number(String::from("3")),
+            TokenKind::Identifier(String::from("e")),
+            TokenKind::Plus,
+            TokenKind::Identifier(String::from("x")),
+            TokenKind::Number(String::from("1000.5e10"))
+        ]);
+    }
+
+    #[test]
+    fn tokenize_prefixed_numbers() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("0xFF+0b1011 0o17 0XdEAd_bEEf 1_000_000", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Number(String::from("255")),
+            TokenKind::Plus,
+            TokenKind::Number(String::from("11")),
+            TokenKind::Number(String::from("15")),
+            TokenKind::Number(String::from("3735928559")),
+            TokenKind::Number(String::from("1000000"))
+        ]);
+    }
+
+    #[test]
+    fn tokenize_bitwise_operators() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("1&2|~3<<4>>5//6/7", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Number(String::from("1")),
+            TokenKind::Ampersand,
+            TokenKind::Number(String::from("2")),
+            TokenKind::Pipe,
+            TokenKind::Tilde,
+            TokenKind::Number(String::from("3")),
+            TokenKind::ShiftLeft,
+            TokenKind::Number(String::from("4")),
+            TokenKind::ShiftRight,
+            TokenKind::Number(String::from("5")),
+            TokenKind::DoubleSlash,
+            TokenKind::Number(String::from("6")),
+            TokenKind::Slash,
+            TokenKind::Number(String::from("7"))
+        ]);
+        assert_eq!(tokenize("1 << 2", Locale::default()).unwrap()[1].span, Span { start: 2, end: 4 });
+    }
+
+    #[test]
+    fn tokenize_operand_is_not_a_number() {
+        use super::*;
+
+        for message in ["..87", "2 + 49..5", "1.2.3", "1,2.3", "0x", "0xFG", "0b102", "0o8", "0x1.5", "1__000", "1_", "1_.5", "2e5_", "0x_1"] {
+            assert_eq!(tokenize(message, Locale::default()).map_err(|e| e.error_type()), Err(CalcErrorType::OperandNotNumber));
+        }
+    }
+
+    #[test]
+    fn tokenize_operand_is_not_a_number_span() {
+        use super::*;
+
+        assert_eq!(tokenize("2 + 49..5", Locale::default()).map_err(|e| e.span()), Err(Some(Span { start: 4, end: 9 })));
+        assert_eq!(tokenize("1 + 0b1021 * 2", Locale::default()).map_err(|e| e.span()), Err(Some(Span { start: 4, end: 10 })));
+    }
+
+    #[test]
+    fn tokenize_locale_numbers() {
+        use super::*;
+
+        let numbers = |message, locale| -> Vec<TokenKind> {
+            tokenize(message, locale).unwrap().into_iter().map(|t| t.kind).filter(|kind| matches!(kind, TokenKind::Number(_))).collect()
+        };
+        let number = |text: &str| TokenKind::Number(String::from(text));
+
+        assert_eq!(numbers("1,234.5 + max(1,234, 2,5)", Locale::DOT), vec![number("1234.5"), number("1234"), number("2"), number("5")]);
+        assert_eq!(numbers("1 234 567,5 + max(1,5, 2) + 0.5", Locale::COMMA), vec![number("1234567.5"), number("1.5"), number("2"), number("0.5")]);
+        assert_eq!(numbers("1.234,5 + 1,5", Locale::from_language("de")), vec![number("1234.5"), number("1.5")]);
+        assert_eq!(tokenize("1.5", Locale::from_language("de")).map_err(|e| e.span()), Err(Some(Span { start: 1, end: 2 })));
+        assert_eq!(numbers("1'234.5", "1'234.5".parse().unwrap()), vec![number("1234.5")]);
+        // Groups have three digits, otherwise the separator isn't a part of the number
+        assert_eq!(numbers("12 34 1 2345", Locale::COMMA), vec![number("12"), number("34"), number("1"), number("2345")]);
+        assert_eq!(numbers("1,23", Locale::DOT), vec![number("1"), number("23")]);
+    }
+
+    #[test]
+    fn tokenize_identifiers() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("sqrt(2) + k_B2", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Identifier(String::from("sqrt")),
+            TokenKind::LeftBracket,
+            TokenKind::Number(String::from("2")),
+            TokenKind::RightBracket,
+            TokenKind::Plus,
+            TokenKind::Identifier(String::from("k_B2"))
+        ]);
+    }
+
+    #[test]
+    fn tokenize_results() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("$3 + _ * ans", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Result(3),
+            TokenKind::Plus,
+            TokenKind::Identifier(String::from("_")),
+            TokenKind::Star,
+            TokenKind::Identifier(String::from("ans"))
+        ]);
+
+        assert_eq!(tokenize("2 + $", Locale::default()).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::OperandNotNumber, Some(Span { start: 4, end: 5 }))));
+    }
+
+    #[test]
+    fn tokenize_comma_in_function_call() {
+        use super::*;
+
+        let kinds: Vec<TokenKind> = tokenize("max(1,5, (2,5))", Locale::default()).unwrap().iter().map(|t| t.kind.clone()).collect();
+        assert_eq!(kinds, vec![
+            TokenKind::Identifier(String::from("max")),
+            TokenKind::LeftBracket,
+            TokenKind::Number(String::from("1")),
+            TokenKind::Comma,
+            TokenKind::Number(String::from("5")),
+            TokenKind::Comma,
+            TokenKind::LeftBracket,
+            TokenKind::Number(String::from("2.5")),
+            TokenKind::RightBracket,
+            TokenKind::RightBracket
+        ]);
+    }
+}
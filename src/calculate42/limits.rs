@@ -0,0 +1,73 @@
+use std::time::Duration;
+use super::lexer::Token;
+use super::{CalcError, CalcErrorType};
+
+/// Bounds of a calculation, so one message can't tie up the calculator
+#[derive(Clone, Copy, Debug, PartialEq)]
+pub struct Limits {
+    /// Length of the input in chars
+    pub max_input_length: usize,
+    pub max_tokens: usize,
+    /// How deep expressions can be one inside another: in brackets, after unary operations, in powers.
+    /// Parsing goes deeper into the stack with every level.
+    pub max_nesting_depth: usize,
+    /// Absolute value of an exponent: 2^1000000 is fine, 2^10000000 isn't
+    pub max_exponent: f64,
+    /// How many operations all expressions of one message can calculate, calls of user functions included
+    pub max_steps: usize,
+    /// How long all expressions of one message can be calculated. Operations with huge numbers take long, so steps aren't enough.
+    pub max_time: Duration
+}
+
+impl Default for Limits {
+    fn default() -> Limits {
+        Limits {
+            max_input_length: 4096,
+            max_tokens: 1000,
+            max_nesting_depth: 100,
+            max_exponent: 1_000_000.0,
+            max_steps: 100_000,
+            max_time: Duration::from_secs(5)
+        }
+    }
+}
+
+impl Limits {
+    /// Checks the length of the input, before it is split into tokens
+    pub fn check_length(&self, message: &str) -> Result<(), CalcError> {
+        if message.chars().count() > self.max_input_length { Err(CalcError::new(CalcErrorType::LimitExceeded)) }
+        else { Ok(()) }
+    }
+
+    /// Points at the first token, which is over the limit
+    pub(crate) fn check_tokens(&self, tokens: &[Token]) -> Result<(), CalcError> {
+        match tokens.get(self.max_tokens) {
+            Some(token) => Err(CalcError::with_span(CalcErrorType::LimitExceeded, token.span)),
+            None => Ok(())
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn check_length() {
+        use super::*;
+
+        let limits = Limits { max_input_length: 5, ..Limits::default() };
+        assert!(limits.check_length("2 + 3").is_ok());
+        assert!(limits.check_length("жжжжж").is_ok());
+        assert_eq!(limits.check_length("2 + 30").map_err(|e| e.error_type()), Err(CalcErrorType::LimitExceeded));
+    }
+
+    #[test]
+    fn check_tokens_count() {
+        use super::*;
+        use super::super::lexer::tokenize;
+        use super::super::locale::Locale;
+
+        let limits = Limits { max_tokens: 3, ..Limits::default() };
+        assert!(limits.check_tokens(&tokenize("2 + 3", Locale::default()).unwrap()).is_ok());
+        assert_eq!(limits.check_tokens(&tokenize("2 + 3 * 4", Locale::default()).unwrap()).map_err(|e| e.span()), Err(Some(super::super::lexer::Span { start: 6, end: 7 })));
+    }
+}
@@ -0,0 +1,138 @@
+use std::str::FromStr;
+
+/// How numbers are written in expressions
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub struct Locale {
+    /// Separates the fractional part: 2.5 or 2,5. Without it both dot and comma do,
+    /// and a comma in brackets of a function call separates arguments.
+    pub decimal_separator: Option<char>,
+    /// Separates groups of three digits in the integer part: 1 234 567
+    pub group_separator: Option<char>
+}
+
+impl Locale {
+    /// 1,234.5
+    pub const DOT: Locale = Locale { decimal_separator: Some('.'), group_separator: Some(',') };
+    /// 1 234,5
+    pub const COMMA: Locale = Locale { decimal_separator: Some(','), group_separator: Some(' ') };
+
+    /// Chooses the locale by a language tag like `en` or `pt-BR`. Unknown languages get both separators.
+    pub fn from_language(code: &str) -> Locale {
+        let code = code.to_lowercase();
+        let language = code.split(['-', '_']).next().unwrap_or_default();
+
+        match (code.as_str(), language) {
+            ("de-ch" | "it-ch", _) | (_, "rm") => Locale { decimal_separator: Some('.'), group_separator: Some('\'') },
+            ("pt-br", _) => Locale { decimal_separator: Some(','), group_separator: Some('.') },
+            (_, "en" | "zh" | "ja" | "ko" | "he" | "hi" | "th" | "ms" | "fil" | "ga") => Locale::DOT,
+            (_, "ru" | "uk" | "be" | "kk" | "uz" | "fr" | "pl" | "cs" | "sk" | "fi" | "sv" | "nb" | "no" | "bg" | "hu" | "lt" | "lv" | "et" | "pt") => Locale::COMMA,
+            (_, "de" | "es" | "it" | "nl" | "id" | "tr" | "da" | "el" | "ro" | "hr" | "sr" | "sl" | "vi" | "az") => {
+                Locale { decimal_separator: Some(','), group_separator: Some('.') }
+            },
+            _ => Locale::default()
+        }
+    }
+
+    /// A dot is a decimal separator with a comma too, unless it groups digits
+    pub fn is_decimal_separator(&self, ch: char) -> bool {
+        match self.decimal_separator {
+            None => ch == '.' || ch == ',',
+            Some(',') => ch == ',' || (ch == '.' && self.group_separator != Some('.')),
+            Some(separator) => ch == separator
+        }
+    }
+
+    /// Non-breaking spaces are spaces too
+    pub fn is_group_separator(&self, ch: char) -> bool {
+        match self.group_separator {
+            Some(' ') => matches!(ch, ' ' | '\u{a0}' | '\u{202f}'),
+            separator => separator == Some(ch)
+        }
+    }
+
+    /// Writes a number in the common way: without group separators and with a dot
+    pub(super) fn normalize(&self, number: &str) -> String {
+        number.chars()
+            .filter(|&ch| !self.is_group_separator(ch))
+            .map(|ch| if self.is_decimal_separator(ch) { '.' } else { ch })
+            .collect()
+    }
+
+    /// One thousand two hundred thirty-four and a half in this locale
+    pub fn example(&self) -> String {
+        match (self.decimal_separator, self.group_separator) {
+            (None, _) => String::from("1234.5 or 1234,5"),
+            (Some(decimal), None) => format!("1234{}5", decimal),
+            (Some(decimal), Some(group)) => format!("1{}234{}5", group, decimal)
+        }
+    }
+}
+
+/// Reads a locale from its example: 1,234.5, 1 234,5, 1'234.5, 1234,5 and so on. `any` accepts both separators.
+impl FromStr for Locale {
+    type Err = String;
+
+    fn from_str(example: &str) -> Result<Locale, String> {
+        let error = || String::from("Unknown locale. Write 1234.5 the way you write numbers: 1,234.5, 1 234,5, 1'234.5, or any for both dot and comma.");
+        if example == "any" { return Ok(Locale::default()) }
+
+        let rest = example.strip_prefix('1').ok_or_else(error)?;
+        let (group_separator, rest) = match rest.strip_prefix("234") {
+            Some(rest) => (None, rest),
+            None => {
+                let group = rest.chars().next().ok_or_else(error)?;
+                (Some(group), rest[group.len_utf8()..].strip_prefix("234").ok_or_else(error)?)
+            }
+        };
+        let decimal_separator = match rest.strip_suffix('5') {
+            Some(".") => '.',
+            Some(",") => ',',
+            _ => return Err(error())
+        };
+
+        match group_separator {
+            Some(group) if group == decimal_separator || !matches!(group, ' ' | '\u{a0}' | '\u{202f}' | '\'' | ',' | '.') => Err(error()),
+            // All kinds of spaces are the same
+            Some(group) if group.is_whitespace() => Ok(Locale { decimal_separator: Some(decimal_separator), group_separator: Some(' ') }),
+            _ => Ok(Locale { decimal_separator: Some(decimal_separator), group_separator })
+        }
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn locale_from_example() {
+        use super::*;
+
+        assert_eq!("1,234.5".parse(), Ok(Locale::DOT));
+        assert_eq!("1\u{a0}234,5".parse(), Ok(Locale::COMMA));
+        assert_eq!("1'234.5".parse(), Ok(Locale { decimal_separator: Some('.'), group_separator: Some('\'') }));
+        assert_eq!("1234,5".parse(), Ok(Locale { decimal_separator: Some(','), group_separator: None }));
+        assert_eq!("any".parse(), Ok(Locale::default()));
+        for example in ["", "1,234,5", "1234", "1x234.5", "2,345.6", "1234.5.5"] {
+            assert!(example.parse::<Locale>().is_err(), "{}", example);
+        }
+    }
+
+    #[test]
+    fn locale_from_language() {
+        use super::*;
+
+        assert_eq!(Locale::from_language("en"), Locale::DOT);
+        assert_eq!(Locale::from_language("ru"), Locale::COMMA);
+        assert_eq!(Locale::from_language("pt-BR"), Locale { decimal_separator: Some(','), group_separator: Some('.') });
+        assert_eq!(Locale::from_language("de-CH").example(), "1'234.5");
+        assert_eq!(Locale::from_language("xx"), Locale::default());
+    }
+
+    #[test]
+    fn normalize() {
+        use super::*;
+
+        assert_eq!(Locale::DOT.normalize("1,234,567.5"), "1234567.5");
+        assert_eq!(Locale::COMMA.normalize("1\u{202f}234,5"), "1234.5");
+        assert_eq!(Locale::from_language("de").normalize("1.234,5"), "1234.5");
+        assert_eq!(Locale::default().normalize("2,5"), "2.5");
+    }
+}
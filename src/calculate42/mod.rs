@@ -1,262 +1,323 @@
-use regex;
 mod calculate_error;
+mod constants;
+mod context;
+mod format;
+mod functions;
+mod lexer;
+mod limits;
+mod locale;
+mod number;
+mod parser;
 
 pub use calculate_error::Error as CalcError;
 pub use calculate_error::ErrorType as CalcErrorType;
-
-#[derive(Clone, Copy, Debug, PartialEq)]
+pub use constants::{Constant, CONSTANTS};
+pub use context::Context as CalcContext;
+pub use format::{Base, Digits, Format, Notation};
+pub use lexer::Span;
+pub use limits::Limits;
+pub use locale::Locale;
+pub use number::{Arithmetic, Number, DEFAULT_PRECISION, MAX_PRECISION};
+use context::UserFunction;
+use functions::Function;
+use num_complex::Complex64;
+use parser::{Expr, Scope, Statement};
+
+#[derive(Clone, Debug, PartialEq)]
 enum Oper {
     Add,
     Sub,
     Mult,
     Div,
     Rem,
+    /// Division rounded down: 7 // 2 = 3
+    IntDiv,
     Exp,
-    Bracket,
-    // TODO: unary negation (right-associative) with priority like Exp
-    Operand(f64)
+    Neg,
+    Pos,
+    /// Factorial, it is postfix: 5!
+    Fact,
+    BitAnd,
+    BitOr,
+    Xor,
+    BitNot,
+    Shl,
+    Shr,
+    Call(Function, usize),
+    Operand(Number)
 }
 
 impl Oper {
+    /// Bitwise operations are calculated after arithmetic ones, like in C and Python: 1 << 2 + 1 = 1 << 3
     fn get_priority(&self) -> u8 {
         match self {
-            Oper::Add => 1,
-            Oper::Sub => 1,
-            Oper::Mult => 2,
-            Oper::Div => 2,
-            Oper::Rem => 2,
-            Oper::Exp => 3,
+            Oper::BitOr => 1,
+            Oper::Xor => 2,
+            Oper::BitAnd => 3,
+            Oper::Shl => 4,
+            Oper::Shr => 4,
+            Oper::Add => 5,
+            Oper::Sub => 5,
+            Oper::Mult => 6,
+            Oper::Div => 6,
+            Oper::IntDiv => 6,
+            Oper::Rem => 6,
+            Oper::Exp => 7,
+            Oper::Neg => 7,
+            Oper::Pos => 7,
+            Oper::BitNot => 7,
+            Oper::Fact => 8,
             _ => 0
         }
     }
-}
 
-pub fn try_calculate(message: &String) -> Result<f64, CalcError> {
-    if !is_math_expr(message) { return Err(CalcError::new(CalcErrorType::NotMathExpr)) }
-    if !are_brackets_agreed(message) { return Err(CalcError::new(CalcErrorType::BracketsNotAgreed)) }
-
-    recursive_calculate(&convert(message)?)
-}
+    /// Right-associative operations are grouped from the right: 2^3^2 = 2^(3^2)
+    fn is_right_associative(&self) -> bool {
+        matches!(self, Oper::Exp | Oper::Neg | Oper::Pos | Oper::BitNot)
+    }
 
-/// Checks if string is a valid math expression 
-fn is_math_expr(message: &String) -> bool {
-    let re = regex::Regex::new(r"^[\d\s\+\-\*/%\(\)\^\.,]+$").unwrap(); // Numbers, whitespaces, +, -, *, /, %, (, )
+    /// How many operands from the stack the operation takes
+    fn get_operands_count(&self) -> usize {
+        match self {
+            Oper::Neg | Oper::Pos | Oper::BitNot | Oper::Fact => 1,
+            Oper::Call(_, count) => *count,
+            Oper::Operand(_) => 0,
+            _ => 2
+        }
+    }
 
-    re.is_match(message.as_str())
-}
+    /// Calculates the operation. There must be exactly as many operands as the operation takes.
+    /// Functions are calculated exactly, if they can be, otherwise with floats,
+    /// then the result is converted back to the arithmetic.
+    /// A real operation without a real result, like sqrt(-1), is calculated with complex numbers, if the context allows it.
+    fn apply(&self, operands: &[Number], context: &CalcContext) -> Number {
+        let arithmetic = context.arithmetic();
+        let result = self.apply_real(operands, arithmetic);
 
-/// Checks if the string has the correct amount and order of brackets
-fn are_brackets_agreed(message: &String) -> bool {
-    let mut left_counter: u32 = 0;
-    let mut right_counter: u32 = 0;
+        if result.is_nan() && !context.is_real_only() && !operands.iter().any(Number::is_nan) {
+            if let Some(value) = self.apply_complex(operands) {
+                return Number::complex(value, arithmetic)
+            }
+        }
+        result
+    }
 
-    for ch in message.chars() {
-        match ch {
-            '(' => left_counter += 1,
-            ')' => right_counter += 1,
-            _ => {}
+    fn apply_real(&self, operands: &[Number], arithmetic: Arithmetic) -> Number {
+        match self {
+            Oper::Add => operands[0].add(&operands[1], arithmetic),
+            Oper::Sub => operands[0].sub(&operands[1], arithmetic),
+            Oper::Mult => operands[0].mul(&operands[1], arithmetic),
+            Oper::Div => operands[0].div(&operands[1], arithmetic),
+            Oper::Rem => operands[0].rem(&operands[1], arithmetic),
+            Oper::IntDiv => operands[0].int_div(&operands[1], arithmetic),
+            Oper::Exp => operands[0].pow(&operands[1], arithmetic),
+            Oper::Neg => operands[0].neg(),
+            Oper::Pos => operands[0].clone(),
+            Oper::Fact => operands[0].factorial(),
+            Oper::BitAnd => operands[0].bit_and(&operands[1]),
+            Oper::BitOr => operands[0].bit_or(&operands[1]),
+            Oper::Xor => operands[0].bit_xor(&operands[1]),
+            Oper::BitNot => operands[0].bit_not(),
+            Oper::Shl => operands[0].shl(&operands[1]),
+            Oper::Shr => operands[0].shr(&operands[1]),
+            Oper::Call(_, _) if operands.iter().any(Number::is_complex) => {
+                self.apply_complex(operands).map_or(Number::Float(f64::NAN), |value| Number::complex(value, arithmetic))
+            },
+            Oper::Call(function, _) => function.apply_exact(operands).unwrap_or_else(|| {
+                let args: Vec<f64> = operands.iter().map(Number::to_f64).collect();
+                Number::from_f64(function.apply(&args), arithmetic)
+            }),
+            Oper::Operand(n) => n.clone()
         }
-        if right_counter > left_counter { return false }
     }
-    
-    if left_counter != right_counter { false }
-    else { true }
+
+    /// Finds out, why the operation has no finite result.
+    /// NaN is a domain error, unless the operands are NaN already.
+    /// Infinity of finite operands is a division by zero, a pole of a logarithm or just a too big number.
+    fn check_result(&self, operands: &[Number], result: &Number) -> Result<(), CalcErrorType> {
+        if result.is_finite() || operands.iter().any(Number::is_nan) { return Ok(()) }
+
+        let is_division_by_zero = match self {
+            Oper::Div | Oper::Rem | Oper::IntDiv => operands[1].is_zero(),
+            Oper::Exp => operands[0].is_zero() && operands[1].is_negative(),
+            _ => false
+        };
+
+        if is_division_by_zero { Err(CalcErrorType::DivisionByZero) }
+        else if result.is_nan() { Err(CalcErrorType::DomainError) }
+        else if !operands.iter().all(Number::is_finite) { Ok(()) }
+        else if let Oper::Call(Function::Ln | Function::Log10 | Function::Log | Function::Atanh, _) = self { Err(CalcErrorType::DomainError) }
+        else { Err(CalcErrorType::Overflow) }
+    }
+
+    /// Calculates powers and functions with complex numbers, if they are defined for them
+    fn apply_complex(&self, operands: &[Number]) -> Option<Complex64> {
+        let args: Vec<Complex64> = operands.iter().map(Number::to_complex).collect();
+
+        let value = match self {
+            Oper::Exp => args[0].powc(args[1]),
+            Oper::Call(function, _) => function.apply_complex(&args)?,
+            _ => return None
+        };
+        if value.is_nan() { None } else { Some(value) }
+    }
 }
 
-/// Tries to convert text to number
-fn try_push_operand(operand: &mut String, stack: &mut Vec<Oper>) -> bool {
+/// Operation or operand in RPN with the span of the sub-expression it stands for
+#[derive(Clone, Debug, PartialEq)]
+struct RpnItem {
+    oper: Oper,
+    span: Span
+}
 
-    if operand.chars().count() <= 15 { 
-        if let Ok(n) = operand.parse() {
-            stack.push(Oper::Operand(n));
-            operand.clear();
-            return true;
+/// What a message turned out to be
+#[derive(Clone, Debug, PartialEq)]
+pub enum Answer {
+    /// Result of an expression or an assignment
+    Number(Number),
+    /// Name of a defined function
+    Function(String),
+    /// Result of an expression, which asks to show it another way: `255 in hex`
+    Formatted(Number, Format)
+}
+
+/// Calculates an expression, assigns its result to a variable or defines a function in the context
+/// Limits of the context are checked at every stage, so a too long input isn't even split into tokens.
+pub fn evaluate(message: &str, context: &mut CalcContext) -> Result<Answer, CalcError> {
+    let limits = context.limits();
+    limits.check_length(message)?;
+    check_brackets(message)?;
+    let tokens = lexer::tokenize(message, context.locale())?;
+    limits.check_tokens(&tokens)?;
+    context.reset_steps();
+
+    match parser::parse_statement(&tokens, context)? {
+        Statement::Expression(expr) => Ok(Answer::Number(calculate(&convert(&expr, context)?, context)?)),
+        Statement::Assignment(name, expr) => {
+            let value = calculate(&convert(&expr, context)?, context)?;
+            context.set_variable(&name, value.clone());
+            Ok(Answer::Number(value))
+        },
+        Statement::Definition(name, parameters, body) => {
+            let text = format!("{}({}) = {}", name, parameters.join(", "), &message[body.span.start..body.span.end]);
+            context.set_function(&name, UserFunction { parameters, body, text });
+            Ok(Answer::Function(name))
+        },
+        Statement::Conversion(expr, radix) => {
+            let value = calculate(&convert(&expr, context)?, context)?;
+            // The width of the chosen base stays, so `-1 in hex` is in two's complement too
+            let mut format = context.format();
+            let width = format.base.and_then(|base| base.width);
+            format.base = if radix == 10 && width.is_none() { None } else { Some(Base { radix, width }) };
+            Ok(Answer::Formatted(value, format))
         }
     }
-    false
 }
 
-/// Converts a string with a *valid* (but not necessarily correct) math expression 
-/// to a stack with an expression in RPN. Tests will show in detail.
-fn convert(math_expr: &String) -> Result<Vec<Oper>, CalcError> {
-    let mut result: Vec<Oper> = Vec::new();
-    let mut temp: Vec<Oper> = Vec::new();
-    let mut operand = String::new();
-
-    for current_ch in math_expr.replace(" ", "").replace(",", ".").chars() {
-        match current_ch {
-            operation_symbol if 
-                operation_symbol == '+' || operation_symbol == '-' || operation_symbol == '*' || 
-                operation_symbol == '/' || operation_symbol == '%' || operation_symbol == '^' || 
-                operation_symbol == '(' => {
-
-                // If found an operation symbol, the previous number has ended, so we will add it to result
-                if operand != "" && !try_push_operand(&mut operand, &mut result) { return Err(CalcError::new(CalcErrorType::OperandNotNumber)) };
-
-                let current_operation = match operation_symbol {
-                    '+' => Oper::Add,
-                    '-' => Oper::Sub,
-                    '*' => Oper::Mult,
-                    '%' => Oper::Rem,
-                    '^' => Oper::Exp,
-                    '(' => Oper::Bracket,
-                    _ => Oper::Div // We don't need to check, the main check in the 'if' above
-                };
-
-                if current_operation != Oper::Bracket {
-                    loop {
-                        match temp.last() {
-                            Some(last_operation) if last_operation.get_priority() >= current_operation.get_priority() => {
-                                result.push(*last_operation);
-                                temp.pop();
-                            }
-                            _ => { break; }
-                        }
-                    }
-                }
+/// Checks if the string has the correct amount and order of brackets.
+/// Points at an extra closing bracket or at the last opening bracket which is not closed.
+fn check_brackets(message: &str) -> Result<(), CalcError> {
+    let mut opened: Vec<usize> = Vec::new();
 
-                temp.push(current_operation);
+    for (i, ch) in message.char_indices() {
+        match ch {
+            '(' => opened.push(i),
+            ')' if opened.pop().is_none() => {
+                return Err(CalcError::with_span(CalcErrorType::BracketsNotAgreed, Span { start: i, end: i + 1 }))
             },
-            ')' => {                
-                // If found a bracket, the previous number has ended, so we will add it to result
-                if operand != "" && !try_push_operand(&mut operand, &mut result) { return Err(CalcError::new(CalcErrorType::OperandNotNumber)) };
-
-                loop {
-                    match temp.last() {
-                        Some(not_bracket) if not_bracket != &Oper::Bracket => {
-                            result.push(*not_bracket);
-                            temp.pop();
-                        },
-                        Some(bracket) if bracket == &Oper::Bracket => {
-                            temp.pop();
-                            break;
-                        }
-                        _ => { break; }
-                    }
-                }
-            }
-            number => {
-                operand.push(number); // If a char is not an operation symbol, it is a number, this fn doesn't check
-            }
+            _ => {}
         }
     }
-    
-    // Don't forget the last number
-    if operand != "" && !try_push_operand(&mut operand, &mut result) { return Err(CalcError::new(CalcErrorType::OperandNotNumber)) };
 
-    // Don't forget operations on the stack
-    temp.reverse();
-    for oper in temp {
-        result.push(oper);
+    match opened.pop() {
+        Some(i) => Err(CalcError::with_span(CalcErrorType::BracketsNotAgreed, Span { start: i, end: i + 1 })),
+        None => Ok(())
     }
-
-    Ok(result)
 }
 
-fn recursive_calculate(rpn_expr: &Vec<Oper>) -> Result<f64, CalcError> {
-    let mut new_rpn_expr: Vec<Oper> = Vec::new();
-    let mut counter: u32 = 0;
-    let mut left: Option<f64> = None;
-    let mut right: Option<f64> = None;
-    let mut was_operation: bool = false;
+/// Converts an expression tree to a stack with an expression in RPN.
+/// Names are replaced with values from the context or constants, user functions are replaced with their results.
+fn convert(tree: &Expr, context: &CalcContext) -> Result<Vec<RpnItem>, CalcError> {
+    let mut result: Vec<RpnItem> = Vec::new();
+    tree.to_rpn(&mut result, &Scope::new(context))?;
 
-    for oper in rpn_expr {
+    Ok(result)
+}
 
-        if was_operation {
-            new_rpn_expr.push(*oper);
-            counter += 1;
-        }
-        else {
-            match oper {
-                Oper::Operand(n) => {
-                    match left {
-                        None => left = Some(*n),
-                        Some(l) => {
-                            if right == None {
-                                right = Some(*n);
-                            }
-                            else {
-                                new_rpn_expr.push(Oper::Operand(l));
-                                counter += 1;
-                                left = right;
-                                right = Some(*n);
-                            }
-                        }
-                    }
-                },
-                _ => {
-                    let left_number: f64;
-                    match left {
-                        Some(n) => left_number = n,
-                        None => return Err(CalcError::new(CalcErrorType::MissedOperand))
-                    }
-                    let right_number: f64;
-                    match right {
-                        Some(n) => right_number = n,
-                        None => return Err(CalcError::new(CalcErrorType::MissedOperand))
-                    }
-
-                    left = None;
-                    right = None;
-                    counter += 1;
-                    was_operation = true;
-                    let result: f64;
-
-                    // TODO: I can't be sure, that the operations went through correctly, if the operands are too big
-                    result = match oper {
-                        Oper::Add => left_number + right_number,
-                        Oper::Sub => left_number - right_number,
-                        Oper::Mult => left_number * right_number,
-                        Oper::Div => left_number / right_number,
-                        Oper::Rem => left_number % right_number,
-                        Oper::Exp => left_number.powf(right_number),
-                        _ => return Err(CalcError::new(CalcErrorType::UnknownError))
-                    };
-
-                    new_rpn_expr.push(Oper::Operand(result));
+/// Calculates an expression in RPN in one pass: operands wait on the stack,
+/// every operation takes the last of them and puts its result back
+fn calculate(rpn_expr: &[RpnItem], context: &CalcContext) -> Result<Number, CalcError> {
+    let mut operands: Vec<(Number, Span)> = Vec::new();
+
+    for item in rpn_expr {
+        match &item.oper {
+            Oper::Operand(n) => operands.push((n.clone(), item.span)),
+            oper => {
+                let count = oper.get_operands_count();
+                if operands.len() < count { return Err(CalcError::with_span(CalcErrorType::MissedOperand, item.span)) }
+
+                let taken: Vec<Number> = operands.drain(operands.len() - count..).map(|(n, _)| n).collect();
+                // A shift is a power of 2 too
+                let is_power = matches!(oper, Oper::Exp | Oper::Shl);
+                if !context.take_step() || (is_power && taken[1].to_complex().norm() > context.limits().max_exponent) {
+                    return Err(CalcError::with_span(CalcErrorType::LimitExceeded, item.span))
+                }
+                let result = oper.apply(&taken, context);
+                if !context.is_ieee() {
+                    oper.check_result(&taken, &result).map_err(|error_type| CalcError::with_span(error_type, item.span))?;
                 }
+                operands.push((result, item.span));
             }
         }
     }
-    
-    match left {
-        Some(n) if right == None => { 
-            new_rpn_expr.push(Oper::Operand(n));
-            counter += 1;
-        }
-        Some(_) if right != None => {
-            return Err(CalcError::new(CalcErrorType::MissedOperation))
-        }
-        _ => {}
-    }
-
-    if counter > 1 {
-        return recursive_calculate(&new_rpn_expr);
-    } 
-    else {
-        let result = new_rpn_expr.pop();
-        match result {
-            Some(operand) => {
-                match operand {
-                    Oper::Operand(number) => {
-                        return Ok(number);
-                    },
-                    _ => return Err(CalcError::new(CalcErrorType::UnknownError))
-                }
-            },
-            _ => return Err(CalcError::new(CalcErrorType::NotMathExpr))
-        }
+
+    // All operations are calculated, so there must be only the result
+    match operands.as_slice() {
+        [] => Err(CalcError::new(CalcErrorType::NotMathExpr)),
+        [(number, _)] => Ok(number.clone()),
+        [_, (_, span), ..] => Err(CalcError::with_span(CalcErrorType::MissedOperation, *span))
     }
 }
 
 #[cfg(test)]
+#[allow(clippy::vec_init_then_push)]
 mod tests {
+    use super::{convert, lexer, parser, Answer, CalcContext, CalcError, Locale, Number, Oper, RpnItem, Span};
+
+    fn operand(n: f64) -> Oper {
+        Oper::Operand(Number::from(n))
+    }
+
+    /// Text of a number, as the user sees it
+    fn answer_text(answer: &Answer) -> String {
+        match answer {
+            Answer::Number(n) => n.to_string(),
+            Answer::Function(name) => name.clone(),
+            Answer::Formatted(n, format) => format.render(n)
+        }
+    }
+
+    /// RPN which is built by hand has no input, so all spans are empty
+    fn spanned(rpn: Vec<Oper>) -> Vec<RpnItem> {
+        rpn.into_iter().map(|oper| RpnItem { oper, span: Span { start: 0, end: 0 } }).collect()
+    }
+
+    fn unspanned(rpn: Vec<RpnItem>) -> Vec<Oper> {
+        rpn.into_iter().map(|item| item.oper).collect()
+    }
+
+    /// Converts a string with an expression without variables
+    fn convert_message(math_expr: &str) -> Result<Vec<Oper>, CalcError> {
+        let tree = parser::parse(&lexer::tokenize(math_expr, Locale::default())?, &CalcContext::default())?;
+        convert(&tree, &CalcContext::default()).map(unspanned)
+    }
+
     #[test]
     fn is_brackets_agreed_correct() {
         use super::*;
 
         for message in ["(2 + 2f)", "3 kk* (3)", "((4) !/(4))", "(5)- ?(5)", "1* nana*1", "word", "another word", ""] {
-            assert_eq!(are_brackets_agreed(&String::from(message)), true);
+            assert!(check_brackets(message).is_ok());
         }
     }
 
@@ -265,7 +326,7 @@ mod tests {
         use super::*;
 
         for message in ["((2 + 2f)", "(3 kk* (3)", "(((4) !/(4))", "((5)- ?(5)", "1* nana*(1", "(word", "another (word", "("] {
-            assert_eq!(are_brackets_agreed(&String::from(message)), false);
+            assert!(check_brackets(message).is_err());
         }
     }
 
@@ -274,7 +335,7 @@ mod tests {
         use super::*;
 
         for message in ["(2) + 2f)", "3) kk* (3)", "((4) !/(4)))", "(5))- ?(5)", ")1* nana*1", "word)", "another) word", ")"] {
-            assert_eq!(are_brackets_agreed(&String::from(message)), false);
+            assert!(check_brackets(message).is_err());
         }
     }
 
@@ -283,7 +344,7 @@ mod tests {
         use super::*;
 
         for message in [")2 + 2f(", "3 kk* )3(", "((4) !/)4)(", ")5(- ?(5)"] {
-            assert_eq!(are_brackets_agreed(&String::from(message)), false);
+            assert!(check_brackets(message).is_err());
         }
     }
 
@@ -292,8 +353,8 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        assert_eq!(convert(&String::from("2387")), Ok(res))
+        res.push(operand(2387.0));
+        assert_eq!(convert_message("2387"), Ok(res))
     }
 
     #[test]
@@ -301,8 +362,8 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.2));
-        assert_eq!(convert(&String::from("2387.2")), Ok(res))
+        res.push(operand(2387.2));
+        assert_eq!(convert_message("2387.2"), Ok(res))
     }
 
     #[test]
@@ -310,35 +371,26 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.2));
-        assert_eq!(convert(&String::from("2387,2")), Ok(res))
+        res.push(operand(2387.2));
+        assert_eq!(convert_message("2387,2"), Ok(res))
     }
 
     #[test]
     fn convert_numbers_with_whitespaces() {
         use super::*;
-
-        let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        assert_eq!(convert(&String::from("2 3 87")), Ok(res))
+        assert_eq!(convert_message("2 3 87").map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation))
     }
 
     #[test]
     fn convert_non_integer_numbers_with_dots_and_whitespaces() {
         use super::*;
-
-        let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.2));
-        assert_eq!(convert(&String::from("23 8 7. 2")), Ok(res))
+        assert_eq!(convert_message("23 8 7. 2").map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation))
     }
 
     #[test]
     fn convert_non_integer_numbers_with_commas_and_whitespaces() {
         use super::*;
-
-        let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.2));
-        assert_eq!(convert(&String::from("2 387 , 2")), Ok(res))
+        assert_eq!(convert_message("2 387 , 2").map_err(|e| e.error_type()), Err(CalcErrorType::OperandNotNumber))
     }
 
     #[test]
@@ -346,10 +398,10 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 + 495"), Ok(res))
     }
 
     #[test]
@@ -357,23 +409,17 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Add);
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(Oper::Pos);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 ++ 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 ++ 495"), Ok(res))
     }
 
     #[test]
     fn convert_numbers_with_plus_extra_operation() {
         use super::*;
-
-        let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Add);
-        res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5+")), Ok(res))
+        assert_eq!(convert_message("2387 + 495+").map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand))
     }
 
     #[test]
@@ -381,12 +427,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Add);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5+ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 + 495+ 43021"), Ok(res))
     }
 
     #[test]
@@ -394,10 +440,10 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Sub);
-        assert_eq!(convert(&String::from("2 3 87 - 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 - 495"), Ok(res))
     }
 
     #[test]
@@ -405,12 +451,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Add);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Sub);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5- 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 + 495- 43021"), Ok(res))
     }
 
     #[test]
@@ -418,12 +464,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Sub);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 - 49 5+ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 - 495+ 43021"), Ok(res))
     }
 
     #[test]
@@ -431,10 +477,10 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Mult);
-        assert_eq!(convert(&String::from("2 3 87 * 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 * 495"), Ok(res))
     }
 
     #[test]
@@ -442,12 +488,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
         res.push(Oper::Mult);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5* 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 + 495* 43021"), Ok(res))
     }
 
     #[test]
@@ -455,22 +501,22 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Mult);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 * 49 5+ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 * 495+ 43021"), Ok(res))
     }
 
     #[test]
     fn convert_numbers_with_div_correct() {
         use super::*;
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Div);
-        assert_eq!(convert(&String::from("2 3 87 / 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 / 495"), Ok(res))
     }
 
     #[test]
@@ -478,12 +524,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Div);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Mult);
-        assert_eq!(convert(&String::from("2 3 87 / 49 5* 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 / 495* 43021"), Ok(res))
     }
 
     #[test]
@@ -491,12 +537,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
         res.push(Oper::Div);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5/ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 + 495/ 43021"), Ok(res))
     }
 
     #[test]
@@ -504,22 +550,22 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Div);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 / 49 5+ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 / 495+ 43021"), Ok(res))
     }
 
     #[test]
     fn convert_numbers_with_rem_correct() {
         use super::*;
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Rem);
-        assert_eq!(convert(&String::from("2 3 87 % 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 % 495"), Ok(res))
     }
 
     #[test]
@@ -527,12 +573,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Rem);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Mult);
-        assert_eq!(convert(&String::from("2 3 87 % 49 5* 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 % 495* 43021"), Ok(res))
     }
 
     #[test]
@@ -540,12 +586,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
         res.push(Oper::Rem);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5% 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 + 495% 43021"), Ok(res))
     }
 
     #[test]
@@ -553,22 +599,22 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Rem);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 % 49 5+ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 % 495+ 43021"), Ok(res))
     }
 
     #[test]
     fn convert_numbers_with_exp_correct() {
         use super::*;
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Exp);
-        assert_eq!(convert(&String::from("2 3 87 ^ 49 5")), Ok(res))
+        assert_eq!(convert_message("2387 ^ 495"), Ok(res))
     }
 
     #[test]
@@ -576,12 +622,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Exp);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Mult);
-        assert_eq!(convert(&String::from("2 3 87 ^ 49 5* 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 ^ 495* 43021"), Ok(res))
     }
 
     #[test]
@@ -589,12 +635,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
         res.push(Oper::Exp);
         res.push(Oper::Mult);
-        assert_eq!(convert(&String::from("2 3 87 * 49 5^ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 * 495^ 43021"), Ok(res))
     }
 
     #[test]
@@ -602,12 +648,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
         res.push(Oper::Exp);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5^ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 + 495^ 43021"), Ok(res))
     }
 
     #[test]
@@ -615,12 +661,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
         res.push(Oper::Exp);
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(43021.0));
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 ^ 49 5+ 43 0 21")), Ok(res))
+        assert_eq!(convert_message("2387 ^ 495+ 43021"), Ok(res))
     }
 
     #[test]
@@ -628,14 +674,69 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
-        res.push(Oper::Operand(1509.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
+        res.push(operand(1509.0));
         res.push(Oper::Exp);
         res.push(Oper::Mult);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + 49 5* 43 0 21 ^15 09")), Ok(res))
+        assert_eq!(convert_message("2387 + 495* 43021 ^1509"), Ok(res))
+    }
+
+    #[test]
+    fn convert_numbers_with_exp_and_exp_correct() {
+        use super::*;
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
+        res.push(Oper::Exp);
+        res.push(Oper::Exp);
+        assert_eq!(convert_message("2387 ^ 495^ 43021"), Ok(res))
+    }
+
+    #[test]
+    fn convert_numbers_with_exp_and_exp_with_brackets_correct() {
+        use super::*;
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(Oper::Exp);
+        res.push(operand(43021.0));
+        res.push(Oper::Exp);
+        assert_eq!(convert_message("(2387 ^ 495)^ 43021"), Ok(res))
+    }
+
+    #[test]
+    fn convert_numbers_with_exp_and_exp_and_mult_correct() {
+        use super::*;
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
+        res.push(Oper::Exp);
+        res.push(Oper::Exp);
+        res.push(operand(1509.0));
+        res.push(Oper::Mult);
+        assert_eq!(convert_message("2387 ^ 495^ 43021 * 1509"), Ok(res))
+    }
+
+    #[test]
+    fn convert_numbers_with_exp_and_unary_minus_correct() {
+        use super::*;
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
+        res.push(Oper::Exp);
+        res.push(Oper::Neg);
+        res.push(Oper::Exp);
+        assert_eq!(convert_message("2387 ^ -495^ 43021"), Ok(res))
     }
 
     #[test]
@@ -643,12 +744,12 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
         res.push(Oper::Sub);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + (49 5- 43 0 21)")), Ok(res))
+        assert_eq!(convert_message("2387 + (495- 43021)"), Ok(res))
     }
 
     #[test]
@@ -656,59 +757,93 @@ mod tests {
         use super::*;
 
         let mut res: Vec<Oper> = Vec::new();
-        res.push(Oper::Operand(2387.0));
-        res.push(Oper::Operand(495.0));
-        res.push(Oper::Operand(43021.0));
-        res.push(Oper::Operand(534.0));
+        res.push(operand(2387.0));
+        res.push(operand(495.0));
+        res.push(operand(43021.0));
+        res.push(operand(534.0));
         res.push(Oper::Add);
         res.push(Oper::Sub);
         res.push(Oper::Add);
-        assert_eq!(convert(&String::from("2 3 87 + (49 5- (43 0 21 +534))")), Ok(res))
+        assert_eq!(convert_message("2387 + (495- (43021 +534))"), Ok(res))
     }
 
     #[test]
-    fn convert_operand_before_an_operation_is_not_a_number() {
+    fn convert_unary_minus_at_start() {
         use super::*;
-        assert_eq!(convert(&String::from("2 3 ..87 + 49 5")), Err(CalcError::new(CalcErrorType::OperandNotNumber)))
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(5.0));
+        res.push(Oper::Neg);
+        res.push(operand(3.0));
+        res.push(Oper::Add);
+        assert_eq!(convert_message("-5 + 3"), Ok(res))
     }
-        
+
     #[test]
-    fn convert_operand_before_a_bracket_is_not_a_number() {
+    fn convert_unary_minus_after_operation() {
         use super::*;
-        assert_eq!(convert(&String::from("(2 3 87 + 49.. 5)")), Err(CalcError::new(CalcErrorType::OperandNotNumber)))
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(2.0));
+        res.push(operand(3.0));
+        res.push(Oper::Neg);
+        res.push(Oper::Mult);
+        assert_eq!(convert_message("2 * -3"), Ok(res))
     }
-        
+
     #[test]
-    fn convert_last_operand_is_not_a_number() {
+    fn convert_unary_minus_and_exp() {
         use super::*;
-        assert_eq!(convert(&String::from("2 3 87 + 49.. 5")), Err(CalcError::new(CalcErrorType::OperandNotNumber)))
+
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(2.0));
+        res.push(operand(2.0));
+        res.push(Oper::Exp);
+        res.push(Oper::Neg);
+        assert_eq!(convert_message("-2^2"), Ok(res))
     }
 
     #[test]
-    fn is_math_expr_looks_like_math_expr_without_whitespaces() {
+    fn convert_unary_minus_before_brackets() {
         use super::*;
 
-        for message in ["2.0+2,0", "3*3", "4/4", "5-5", "1**1", "6//6", "7%7", ")8(8", "9^9"] {
-            assert!(is_math_expr(&String::from(message)));
-        }
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(3.0));
+        res.push(operand(4.0));
+        res.push(operand(1.0));
+        res.push(Oper::Sub);
+        res.push(Oper::Neg);
+        res.push(Oper::Mult);
+        assert_eq!(convert_message("3 * -(4 - 1)"), Ok(res))
     }
 
     #[test]
-    fn is_math_expr_looks_like_math_expr_with_whitespaces() {
+    fn convert_two_unary_operations_in_a_row() {
         use super::*;
 
-        for message in ["2.0 + 2, 2", "3 * 3", "4 /4", "5- 5", "1* *1", "6    //6", "7%   7", ") 8(    8", "9^ 9"] {
-            assert!(is_math_expr(&String::from(message)));
-        }
+        let mut res: Vec<Oper> = Vec::new();
+        res.push(operand(5.0));
+        res.push(Oper::Pos);
+        res.push(Oper::Neg);
+        assert_eq!(convert_message("-+5"), Ok(res))
     }
 
     #[test]
-    fn is_math_expr_definitely_not_math_expr() {
+    fn convert_operand_before_an_operation_is_not_a_number() {
         use super::*;
-
-        for message in ["2 + 2f", "3 kk* 3", "4 !/4", "5- ?5", "1* nana*1", "word", "another word", ""] {
-            assert_eq!(is_math_expr(&String::from(message)), false);
-        }
+        assert_eq!(convert_message("2 3 ..87 + 495").map_err(|e| e.error_type()), Err(CalcErrorType::OperandNotNumber))
+    }
+        
+    #[test]
+    fn convert_operand_before_a_bracket_is_not_a_number() {
+        use super::*;
+        assert_eq!(convert_message("(2387 + 49.. 5)").map_err(|e| e.error_type()), Err(CalcErrorType::OperandNotNumber))
+    }
+        
+    #[test]
+    fn convert_last_operand_is_not_a_number() {
+        use super::*;
+        assert_eq!(convert_message("2387 + 49.. 5").map_err(|e| e.error_type()), Err(CalcErrorType::OperandNotNumber))
     }
 
     #[test]
@@ -716,7 +851,7 @@ mod tests {
         use super::*;
         
         let rpn: Vec<Oper> = Vec::new();
-        assert_eq!(recursive_calculate(&rpn), Err(CalcError::new(CalcErrorType::NotMathExpr)));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::NotMathExpr));
     }
 
     #[test]
@@ -724,8 +859,8 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        assert_eq!(recursive_calculate(&rpn), Ok(189.0));
+        rpn.push(operand(189.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(189.0)));
     }
 
     #[test]
@@ -733,9 +868,9 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
-        assert_eq!(recursive_calculate(&rpn), Err(CalcError::new(CalcErrorType::MissedOperation)));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation));
     }
 
     #[test]
@@ -743,10 +878,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
         rpn.push(Oper::Add);
-        assert_eq!(recursive_calculate(&rpn), Ok(719.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(719.0)));
     }
 
     #[test]
@@ -754,9 +889,9 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
+        rpn.push(operand(189.0));
         rpn.push(Oper::Add);
-        assert_eq!(recursive_calculate(&rpn), Err(CalcError::new(CalcErrorType::MissedOperand)));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand));
     }
 
     #[test]
@@ -765,7 +900,7 @@ mod tests {
         
         let mut rpn: Vec<Oper> = Vec::new();
         rpn.push(Oper::Add);
-        assert_eq!(recursive_calculate(&rpn), Err(CalcError::new(CalcErrorType::MissedOperand)));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand));
     }
 
     #[test]
@@ -773,11 +908,11 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(325.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(325.0));
         rpn.push(Oper::Add);
-        assert_eq!(recursive_calculate(&rpn), Err(CalcError::new(CalcErrorType::MissedOperation)));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation));
     }
 
     #[test]
@@ -785,11 +920,11 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Add);
-        assert_eq!(recursive_calculate(&rpn), Err(CalcError::new(CalcErrorType::MissedOperand)));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand));
     }
 
     #[test]
@@ -797,12 +932,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(325.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(325.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Add);
-        assert_eq!(recursive_calculate(&rpn), Ok(1044.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(1044.0)));
     }
 
     #[test]
@@ -810,10 +945,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(189.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(189.0));
         rpn.push(Oper::Sub);
-        assert_eq!(recursive_calculate(&rpn), Ok(341.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(341.0)));
     }
 
     #[test]
@@ -821,10 +956,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
         rpn.push(Oper::Sub);
-        assert_eq!(recursive_calculate(&rpn), Ok(-341.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-341.0)));
     }
 
     #[test]
@@ -832,12 +967,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
         rpn.push(Oper::Add);
-        rpn.push(Oper::Operand(325.0));
+        rpn.push(operand(325.0));
         rpn.push(Oper::Sub);
-        assert_eq!(recursive_calculate(&rpn), Ok(394.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(394.0)));
     }
 
     #[test]
@@ -845,12 +980,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(325.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(325.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Sub);
-        assert_eq!(recursive_calculate(&rpn), Ok(-666.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-666.0)));
     }
 
     #[test]
@@ -858,10 +993,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(189.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(189.0));
         rpn.push(Oper::Mult);
-        assert_eq!(recursive_calculate(&rpn), Ok(100170.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(100170.0)));
     }
 
     #[test]
@@ -869,10 +1004,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-189.0));
-        rpn.push(Oper::Operand(530.0));
+        rpn.push(operand(-189.0));
+        rpn.push(operand(530.0));
         rpn.push(Oper::Mult);
-        assert_eq!(recursive_calculate(&rpn), Ok(-100170.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-100170.0)));
     }
 
     #[test]
@@ -880,10 +1015,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-189.0));
-        rpn.push(Oper::Operand(-530.0));
+        rpn.push(operand(-189.0));
+        rpn.push(operand(-530.0));
         rpn.push(Oper::Mult);
-        assert_eq!(recursive_calculate(&rpn), Ok(100170.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(100170.0)));
     }
 
     #[test]
@@ -891,12 +1026,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
         rpn.push(Oper::Add);
-        rpn.push(Oper::Operand(325.0));
+        rpn.push(operand(325.0));
         rpn.push(Oper::Mult);
-        assert_eq!(recursive_calculate(&rpn), Ok(233675.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(233675.0)));
     }
 
     #[test]
@@ -904,12 +1039,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(189.0));
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(325.0));
+        rpn.push(operand(189.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(325.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Mult);
-        assert_eq!(recursive_calculate(&rpn), Ok(161595.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(161595.0)));
     }
 
     #[test]
@@ -917,10 +1052,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(106.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(106.0));
         rpn.push(Oper::Div);
-        assert_eq!(recursive_calculate(&rpn), Ok(5.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(5.0)));
     }
 
     #[test]
@@ -928,10 +1063,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(-106.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(-106.0));
         rpn.push(Oper::Div);
-        assert_eq!(recursive_calculate(&rpn), Ok(-5.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-5.0)));
     }
 
     #[test]
@@ -939,10 +1074,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-530.0));
-        rpn.push(Oper::Operand(-106.0));
+        rpn.push(operand(-530.0));
+        rpn.push(operand(-106.0));
         rpn.push(Oper::Div);
-        assert_eq!(recursive_calculate(&rpn), Ok(5.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(5.0)));
     }
 
     #[test]
@@ -950,12 +1085,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(513.0));
-        rpn.push(Oper::Operand(17.0));
+        rpn.push(operand(513.0));
+        rpn.push(operand(17.0));
         rpn.push(Oper::Add);
-        rpn.push(Oper::Operand(106.0));
+        rpn.push(operand(106.0));
         rpn.push(Oper::Div);
-        assert_eq!(recursive_calculate(&rpn), Ok(5.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(5.0)));
     }
 
     #[test]
@@ -963,12 +1098,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(530.0));
-        rpn.push(Oper::Operand(98.0));
-        rpn.push(Oper::Operand(8.0));
+        rpn.push(operand(530.0));
+        rpn.push(operand(98.0));
+        rpn.push(operand(8.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Div);
-        assert_eq!(recursive_calculate(&rpn), Ok(5.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(5.0)));
     }
 
     #[test]
@@ -976,10 +1111,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(533.0));
-        rpn.push(Oper::Operand(106.0));
+        rpn.push(operand(533.0));
+        rpn.push(operand(106.0));
         rpn.push(Oper::Rem);
-        assert_eq!(recursive_calculate(&rpn), Ok(3.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(3.0)));
     }
 
     #[test]
@@ -987,10 +1122,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-533.0));
-        rpn.push(Oper::Operand(106.0));
+        rpn.push(operand(-533.0));
+        rpn.push(operand(106.0));
         rpn.push(Oper::Rem);
-        assert_eq!(recursive_calculate(&rpn), Ok(-3.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-3.0)));
     }
         
     #[test]
@@ -998,10 +1133,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(533.0));
-        rpn.push(Oper::Operand(-106.0));
+        rpn.push(operand(533.0));
+        rpn.push(operand(-106.0));
         rpn.push(Oper::Rem);
-        assert_eq!(recursive_calculate(&rpn), Ok(3.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(3.0)));
     }
 
     #[test]
@@ -1009,10 +1144,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-533.0));
-        rpn.push(Oper::Operand(-106.0));
+        rpn.push(operand(-533.0));
+        rpn.push(operand(-106.0));
         rpn.push(Oper::Rem);
-        assert_eq!(recursive_calculate(&rpn), Ok(-3.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-3.0)));
     }
 
     #[test]
@@ -1020,12 +1155,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(513.0));
-        rpn.push(Oper::Operand(20.0));
+        rpn.push(operand(513.0));
+        rpn.push(operand(20.0));
         rpn.push(Oper::Add);
-        rpn.push(Oper::Operand(106.0));
+        rpn.push(operand(106.0));
         rpn.push(Oper::Rem);
-        assert_eq!(recursive_calculate(&rpn), Ok(3.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(3.0)));
     }
 
     #[test]
@@ -1033,12 +1168,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(533.0));
-        rpn.push(Oper::Operand(98.0));
-        rpn.push(Oper::Operand(8.0));
+        rpn.push(operand(533.0));
+        rpn.push(operand(98.0));
+        rpn.push(operand(8.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Rem);
-        assert_eq!(recursive_calculate(&rpn), Ok(3.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(3.0)));
     }
 
     #[test]
@@ -1046,10 +1181,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(5.0));
-        rpn.push(Oper::Operand(3.0));
+        rpn.push(operand(5.0));
+        rpn.push(operand(3.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(125.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(125.0)));
     }
 
     #[test]
@@ -1057,10 +1192,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-5.0));
-        rpn.push(Oper::Operand(3.0));
+        rpn.push(operand(-5.0));
+        rpn.push(operand(3.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(-125.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-125.0)));
     }
         
     #[test]
@@ -1068,10 +1203,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-5.0));
-        rpn.push(Oper::Operand(4.0));
+        rpn.push(operand(-5.0));
+        rpn.push(operand(4.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(625.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(625.0)));
     }
 
     #[test]
@@ -1079,10 +1214,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(5.0));
-        rpn.push(Oper::Operand(-2.0));
+        rpn.push(operand(5.0));
+        rpn.push(operand(-2.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(1.0 / 25.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(1.0 / 25.0)));
     }
 
     #[test]
@@ -1090,10 +1225,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-5.0));
-        rpn.push(Oper::Operand(-2.0));
+        rpn.push(operand(-5.0));
+        rpn.push(operand(-2.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(1.0 / 25.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(1.0 / 25.0)));
     }
 
     #[test]
@@ -1101,10 +1236,10 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(-5.0));
-        rpn.push(Oper::Operand(-3.0));
+        rpn.push(operand(-5.0));
+        rpn.push(operand(-3.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(-1.0 / 125.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-1.0 / 125.0)));
     }
 
     #[test]
@@ -1112,12 +1247,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(4.0));
-        rpn.push(Oper::Operand(1.0));
+        rpn.push(operand(4.0));
+        rpn.push(operand(1.0));
         rpn.push(Oper::Add);
-        rpn.push(Oper::Operand(3.0));
+        rpn.push(operand(3.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(125.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(125.0)));
     }
 
     #[test]
@@ -1125,12 +1260,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(5.0));
-        rpn.push(Oper::Operand(2.0));
-        rpn.push(Oper::Operand(1.0));
+        rpn.push(operand(5.0));
+        rpn.push(operand(2.0));
+        rpn.push(operand(1.0));
         rpn.push(Oper::Add);
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(125.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(125.0)));
     }
 
     #[test]
@@ -1138,12 +1273,12 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(4.0));
-        rpn.push(Oper::Operand(2.0));
+        rpn.push(operand(4.0));
+        rpn.push(operand(2.0));
         rpn.push(Oper::Mult);
-        rpn.push(Oper::Operand(2.0));
+        rpn.push(operand(2.0));
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(64.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(64.0)));
     }
 
     #[test]
@@ -1151,27 +1286,747 @@ mod tests {
         use super::*;
         
         let mut rpn: Vec<Oper> = Vec::new();
-        rpn.push(Oper::Operand(2.0));
-        rpn.push(Oper::Operand(2.0));
-        rpn.push(Oper::Operand(3.0));
+        rpn.push(operand(2.0));
+        rpn.push(operand(2.0));
+        rpn.push(operand(3.0));
         rpn.push(Oper::Mult);
         rpn.push(Oper::Exp);
-        assert_eq!(recursive_calculate(&rpn), Ok(64.0));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(64.0)));
+    }
+
+    #[test]
+    fn calculate_neg_correct() {
+        use super::*;
+        
+        let mut rpn: Vec<Oper> = Vec::new();
+        rpn.push(operand(5.0));
+        rpn.push(Oper::Neg);
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-5.0)));
     }
 
     #[test]
-    fn try_calculate_not_math_expr() {
+    fn calculate_pos_correct() {
+        use super::*;
+        
+        let mut rpn: Vec<Oper> = Vec::new();
+        rpn.push(operand(5.0));
+        rpn.push(Oper::Pos);
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(5.0)));
+    }
+
+    #[test]
+    fn calculate_neg_right_operand_correct() {
+        use super::*;
+        
+        let mut rpn: Vec<Oper> = Vec::new();
+        rpn.push(operand(2.0));
+        rpn.push(operand(3.0));
+        rpn.push(Oper::Neg);
+        rpn.push(Oper::Mult);
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(-6.0)));
+    }
+
+    #[test]
+    fn calculate_neg_without_operand() {
+        use super::*;
+        
+        let mut rpn: Vec<Oper> = Vec::new();
+        rpn.push(Oper::Neg);
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand));
+    }
+
+    #[test]
+    fn evaluate_unary_minus() {
+        use super::*;
+
+        assert_eq!(evaluate("-5 + 3", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-2.0))));
+        assert_eq!(evaluate("2 * -3", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-6.0))));
+        assert_eq!(evaluate("-2^2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-4.0))));
+        assert_eq!(evaluate("2^-1", &mut CalcContext::default()), Ok(Answer::Number(Number::from(0.5))));
+        assert_eq!(evaluate("3 * -(4 - 1)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-9.0))));
+        assert_eq!(evaluate("--5", &mut CalcContext::default()), Ok(Answer::Number(Number::from(5.0))));
+        assert_eq!(evaluate("(-5)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-5.0))));
+    }
+
+    #[test]
+    fn evaluate_unary_plus() {
+        use super::*;
+
+        assert_eq!(evaluate("+5", &mut CalcContext::default()), Ok(Answer::Number(Number::from(5.0))));
+        assert_eq!(evaluate("2 - +3", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-1.0))));
+        assert_eq!(evaluate("-+5", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-5.0))));
+    }
+
+    #[test]
+    fn evaluate_not_math_expr() {
         use super::*;
         
         let input = String::from("not a math expression");
-        assert_eq!(try_calculate(&input), Err(CalcError::new(CalcErrorType::NotMathExpr)));
+        assert_eq!(evaluate(&input, &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::NotMathExpr));
     }
 
     #[test]
-    fn try_calculate_brackets_not_agreed() {
+    fn evaluate_brackets_not_agreed() {
         use super::*;
         
         let input = String::from("(2 + 2(");
-        assert_eq!(try_calculate(&input), Err(CalcError::new(CalcErrorType::BracketsNotAgreed)));
+        assert_eq!(evaluate(&input, &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::BracketsNotAgreed));
+    }
+
+    #[test]
+    fn evaluate_numbers_are_not_merged() {
+        use super::*;
+        
+        assert_eq!(evaluate("2 3", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation));
+        assert_eq!(evaluate("2 + 2 3", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation));
+    }
+
+    #[test]
+    fn evaluate_correct() {
+        use super::*;
+        
+        assert_eq!(evaluate("(2 + 2) * 2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(8.0))));
+        assert_eq!(evaluate("10 - 4 - 3", &mut CalcContext::default()), Ok(Answer::Number(Number::from(3.0))));
+        assert_eq!(evaluate("2,5 * 2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(5.0))));
+    }
+
+    #[test]
+    fn evaluate_error_spans() {
+        use super::*;
+        
+        for (message, span) in [
+            ("2 + * 3", Span { start: 4, end: 5 }),
+            ("(2 + 2(", Span { start: 6, end: 7 }),
+            ("2 + 3)", Span { start: 5, end: 6 }),
+            ("2 + 4..5", Span { start: 4, end: 8 }),
+            ("2 + x", Span { start: 4, end: 5 })
+        ] {
+            assert_eq!(evaluate(message, &mut CalcContext::default()).map_err(|e| e.span()), Err(Some(span)), "{}", message);
+        }
+    }
+
+    #[test]
+    fn evaluate_error_render() {
+        use super::*;
+        
+        let message = "2 + * 3";
+        assert_eq!(evaluate(message, &mut CalcContext::default()).unwrap_err().render(message), "2 + * 3\n    ^ Missed operand.");
+    }
+
+    #[test]
+    fn calculate_error_span_is_operation_sub_expression() {
+        use super::*;
+        
+        let rpn = vec![RpnItem { oper: Oper::Add, span: Span { start: 2, end: 5 } }];
+        assert_eq!(calculate(&rpn, &CalcContext::default()).map_err(|e| e.span()), Err(Some(Span { start: 2, end: 5 })));
+    }
+
+    #[test]
+    fn evaluate_exp_is_right_associative() {
+        use super::*;
+        
+        assert_eq!(evaluate("2^3^2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(512.0))));
+        assert_eq!(evaluate("(2^3)^2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(64.0))));
+        assert_eq!(evaluate("2^3^2 / 2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(256.0))));
+        assert_eq!(evaluate("-2^2^2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-16.0))));
+        assert_eq!(evaluate("2^-1^2", &mut CalcContext::default()), Ok(Answer::Number(Number::from(0.5))));
+    }
+
+    #[test]
+    fn calculate_call_correct() {
+        use super::*;
+        
+        let mut rpn: Vec<Oper> = Vec::new();
+        rpn.push(operand(1.0));
+        rpn.push(operand(5.0));
+        rpn.push(operand(3.0));
+        rpn.push(Oper::Call(Function::Max, 2));
+        rpn.push(Oper::Add);
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()), Ok(Number::from(6.0)));
+    }
+
+    #[test]
+    fn calculate_call_too_few_operands() {
+        use super::*;
+        
+        let mut rpn: Vec<Oper> = Vec::new();
+        rpn.push(operand(1.0));
+        rpn.push(Oper::Call(Function::Log, 2));
+        assert_eq!(calculate(&spanned(rpn), &CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand));
+    }
+
+    #[test]
+    fn evaluate_functions() {
+        use super::*;
+        
+        assert_eq!(evaluate("sqrt(16) + 1", &mut CalcContext::default()), Ok(Answer::Number(Number::from(5.0))));
+        assert_eq!(evaluate("2 * sqrt(2 + 2)^3", &mut CalcContext::default()), Ok(Answer::Number(Number::from(16.0))));
+        assert_eq!(evaluate("-abs(-3)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(-3.0))));
+        assert_eq!(evaluate("log(2, 1024)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(10.0))));
+        assert_eq!(evaluate("max(1, 7, 3) - min(4, 2.5)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(4.5))));
+        assert_eq!(evaluate("round(floor(2.7) + ceil(0.2))", &mut CalcContext::default()), Ok(Answer::Number(Number::from(3.0))));
+        assert_eq!(evaluate("sin(0) + cos(0)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(1.0))));
+        assert_eq!(evaluate("ln(exp(2))", &mut CalcContext::default()), Ok(Answer::Number(Number::from(2.0))));
+    }
+
+    #[test]
+    fn evaluate_functions_errors() {
+        use super::*;
+        
+        assert_eq!(evaluate("sqrt(1, 2)", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::WrongArgumentsCount));
+        assert_eq!(evaluate("log(8)", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::WrongArgumentsCount));
+        assert_eq!(evaluate("max()", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::WrongArgumentsCount));
+        assert_eq!(evaluate("sqroot(4)", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::UnknownFunction));
+        assert_eq!(evaluate("max(1,5)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(5.0))));
+    }
+
+    #[test]
+    fn evaluate_constants() {
+        use super::*;
+        
+        assert_eq!(evaluate("pi", &mut CalcContext::default()), Ok(Answer::Number(Number::from(std::f64::consts::PI))));
+        assert_eq!(evaluate("2 * pi - tau", &mut CalcContext::default()), Ok(Answer::Number(Number::from(0.0))));
+        assert_eq!(evaluate("ln(e)", &mut CalcContext::default()), Ok(Answer::Number(Number::from(1.0))));
+        assert_eq!(evaluate("-inf", &mut CalcContext::default()), Ok(Answer::Number(Number::from(f64::NEG_INFINITY))));
+        assert_eq!(evaluate("c / 1000", &mut CalcContext::default()), Ok(Answer::Number(Number::from(299792.458))));
+        assert_eq!(evaluate("k_B * N_A", &mut CalcContext::default()), Ok(Answer::Number(Number::from(1.380649e-23 * 6.02214076e23))));
+        assert_eq!(evaluate("phi^2 - phi", &mut CalcContext::default()), Ok(Answer::Number(Number::from(1.0))));
+    }
+
+    #[test]
+    fn evaluate_constants_errors() {
+        use super::*;
+        
+        assert_eq!(evaluate("2 * pie", &mut CalcContext::default()).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::UnknownName, Some(Span { start: 4, end: 7 }))));
+        assert_eq!(evaluate("pi(2)", &mut CalcContext::default()).map_err(|e| e.error_type()), Err(CalcErrorType::UnknownFunction));
+    }
+
+    #[test]
+    fn evaluate_variables() {
+        use super::*;
+        
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("x = 3.5", &mut context), Ok(Answer::Number(Number::from(3.5))));
+        assert_eq!(evaluate("x * 2", &mut context), Ok(Answer::Number(Number::from(7.0))));
+        assert_eq!(evaluate("y = x + 1", &mut context), Ok(Answer::Number(Number::from(4.5))));
+        assert_eq!(evaluate("x = x * y", &mut context), Ok(Answer::Number(Number::from(15.75))));
+        assert_eq!(context.variables().collect::<Vec<(&str, &Number)>>(), vec![("x", &Number::from(15.75)), ("y", &Number::from(4.5))]);
+    }
+
+    #[test]
+    fn evaluate_variables_shadow_constants() {
+        use super::*;
+        
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("h = 10", &mut context), Ok(Answer::Number(Number::from(10.0))));
+        assert_eq!(evaluate("h * 2", &mut context), Ok(Answer::Number(Number::from(20.0))));
+
+        context.clear();
+        assert_eq!(evaluate("h", &mut context), Ok(Answer::Number(Number::from(6.62607015e-34))));
+    }
+
+    #[test]
+    fn evaluate_variables_errors() {
+        use super::*;
+        
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("z * 2", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::UnknownName));
+        assert_eq!(evaluate("x = z * 2", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::UnknownName));
+        assert_eq!(context.get_variable("x"), None);
+    }
+
+    #[test]
+    fn evaluate_previous_results() {
+        use super::*;
+        
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("ans", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::UnknownName));
+
+        context.push_result(Number::from(8.0));
+        assert_eq!(evaluate("ans / 4", &mut context), Ok(Answer::Number(Number::from(2.0))));
+        context.push_result(Number::from(2.0));
+        assert_eq!(evaluate("_ * 3", &mut context), Ok(Answer::Number(Number::from(6.0))));
+        assert_eq!(evaluate("$1 + $2", &mut context), Ok(Answer::Number(Number::from(10.0))));
+        assert_eq!(evaluate("$3", &mut context).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::UnknownName, Some(Span { start: 0, end: 2 }))));
     }
-}
\ No newline at end of file
+
+    #[test]
+    fn evaluate_user_functions() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("f(x, y) = x^2 + y", &mut context), Ok(Answer::Function(String::from("f"))));
+        assert_eq!(evaluate("f(3, 1)", &mut context), Ok(Answer::Number(Number::from(10.0))));
+        assert_eq!(evaluate("2 * f(1 + 1, f(1, 0))", &mut context), Ok(Answer::Number(Number::from(10.0))));
+        assert_eq!(evaluate("g(x) = f(x, x) - y", &mut context), Ok(Answer::Function(String::from("g"))));
+        assert_eq!(evaluate("y = 2", &mut context), Ok(Answer::Number(Number::from(2.0))));
+        assert_eq!(evaluate("g(2)", &mut context), Ok(Answer::Number(Number::from(4.0))));
+        assert_eq!(context.functions().collect::<Vec<&str>>(), vec!["f(x, y) = x^2 + y", "g(x) = f(x, x) - y"]);
+    }
+
+    #[test]
+    fn evaluate_user_functions_shadow_built_in() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("sqrt(x) = x / 2", &mut context), Ok(Answer::Function(String::from("sqrt"))));
+        assert_eq!(evaluate("sqrt(16)", &mut context), Ok(Answer::Number(Number::from(8.0))));
+
+        context.clear();
+        assert_eq!(evaluate("sqrt(16)", &mut context), Ok(Answer::Number(Number::from(4.0))));
+    }
+
+    #[test]
+    fn evaluate_user_functions_errors() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("f(x) = x + 1", &mut context), Ok(Answer::Function(String::from("f"))));
+        assert_eq!(evaluate("g(x) = x + z", &mut context), Ok(Answer::Function(String::from("g"))));
+        assert_eq!(evaluate("r(x) = r(x) + 1", &mut context), Ok(Answer::Function(String::from("r"))));
+
+        for (message, error_type, span) in [
+            ("2 + f(1, 2)", CalcErrorType::WrongArgumentsCount, Span { start: 4, end: 11 }),
+            ("2 + h(1)", CalcErrorType::UnknownFunction, Span { start: 4, end: 8 }),
+            ("2 + g(1)", CalcErrorType::UnknownName, Span { start: 4, end: 8 }),
+            ("f(1 + z)", CalcErrorType::UnknownName, Span { start: 6, end: 7 }),
+            ("2 + r(1)", CalcErrorType::RecursionTooDeep, Span { start: 4, end: 8 })
+        ] {
+            assert_eq!(evaluate(message, &mut context).map_err(|e| (e.error_type(), e.span())), Err((error_type, Some(span))), "{}", message);
+        }
+    }
+
+    #[test]
+    fn evaluate_variable_shadows_previous_result() {
+        use super::*;
+        
+        let mut context = CalcContext::default();
+        context.push_result(Number::from(8.0));
+        assert_eq!(evaluate("ans = 1", &mut context), Ok(Answer::Number(Number::from(1.0))));
+        assert_eq!(evaluate("ans", &mut context), Ok(Answer::Number(Number::from(1.0))));
+    }
+
+    #[test]
+    fn evaluate_decimal() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        for (message, result) in [
+            ("0.1 + 0.2", "0.3"),
+            ("0,7 * 3 - 2", "0.1"),
+            ("123456789012345678901234567890 + 1", "123456789012345678901234567891"),
+            ("1 / 4 + 2^-2", "0.5"),
+            ("sqrt(2.25)", "1.5"),
+            ("x = 0.1 * 3", "0.3"),
+            ("x - 0.3", "0")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+    }
+
+    #[test]
+    fn evaluate_arithmetic_changes() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("x = 0.1", &mut context), Ok(Answer::Number(Number::from(0.1))));
+        assert_eq!(evaluate("0.1 + 0.2", &mut context), Ok(Answer::Number(Number::from(0.30000000000000004))));
+
+        context.set_arithmetic(Arithmetic::Decimal(3));
+        assert_eq!(evaluate("x + 0.2", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("0.3")));
+        assert_eq!(evaluate("2 / 3", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("0.667")));
+    }
+
+    #[test]
+    fn evaluate_big_integers() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        for (message, result) in [
+            ("2^200", "1606938044258990275541962092341162602522202993782792835301376"),
+            ("50!", "30414093201713378043612608166064768844377641568960512000000000000"),
+            ("123456789012345678901234567890 * 3", "370370367037037036703703703670"),
+            ("2^64 / 2^32", "4294967296"),
+            ("-3! + 3!^2 + 2^3!", "94"),
+            ("abs(-10^20)", "100000000000000000000"),
+            ("max(10^20, 10^20 + 1)", "100000000000000000001"),
+            ("7 / 2", "3.5"),
+            ("2^64 + 0.5", "18446744073709552000")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+    }
+
+    #[test]
+    fn evaluate_rational() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Rational);
+        for (message, result) in [
+            ("1/3 + 1/6", "1/2"),
+            ("0.1 + 0.2", "3/10"),
+            ("(2/3)^-2", "9/4"),
+            ("x = 3/4", "3/4"),
+            ("x * 4", "3"),
+            ("max(1/3, 0.3)", "1/3"),
+            ("floor(7/2)", "3"),
+            ("sqrt(1/4)", "0.5")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+    }
+
+    #[test]
+    fn evaluate_complex() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        for (message, result) in [
+            ("sqrt(-1)", "i"),
+            ("sqrt(-4)", "2i"),
+            ("i^2", "-1"),
+            ("i * i + 1", "0"),
+            ("(3+4i)*(1-2i)", "11 - 2i"),
+            ("(1 + i) / (1 - i)", "i"),
+            ("-i", "-i"),
+            ("2 - 3i", "2 - 3i"),
+            ("abs(3 + 4i)", "5"),
+            ("z = 1 + 2i", "1 + 2i"),
+            ("z - 2i", "1"),
+            ("ln(-1)", "3.141592653589793i"),
+            ("i = 5", "5"),
+            ("i + 1", "6")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+        assert!(matches!(evaluate("log(-2, 8)", &mut context), Ok(Answer::Number(Number::Complex(_)))));
+        assert_eq!(evaluate("log(0, 5)", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::DomainError));
+    }
+
+    #[test]
+    fn evaluate_number_literals() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        for (message, result) in [
+            ("6.02e23 / 2", "301000000000000000000000"),
+            ("1.5E-3 * 2", "0.003"),
+            ("0xFF + 0b1011 - 0o17", "251"),
+            ("1_000_000 * 2", "2000000"),
+            ("2e5 - 2e", "199994.563436343")
+        ] {
+            assert!(evaluate(message, &mut context).map(|answer| answer_text(&answer)).is_ok_and(|text| text.starts_with(result)), "{}", message);
+        }
+
+        context.set_arithmetic(Arithmetic::Rational);
+        assert_eq!(evaluate("25e-2", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("1/4")));
+        context.set_arithmetic(Arithmetic::Float);
+        assert_eq!(evaluate("6.02e23", &mut context), Ok(Answer::Number(Number::from(6.02e23))));
+
+        for (message, error_type, span) in [
+            ("1 + 0xZ", CalcErrorType::OperandNotNumber, Span { start: 4, end: 7 }),
+            ("1__0", CalcErrorType::OperandNotNumber, Span { start: 0, end: 4 }),
+            ("2 * 1e10000000", CalcErrorType::LimitExceeded, Span { start: 4, end: 14 })
+        ] {
+            assert_eq!(evaluate(message, &mut context).map_err(|e| (e.error_type(), e.span())), Err((error_type, Some(span))), "{}", message);
+        }
+    }
+
+    #[test]
+    fn evaluate_locale() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        context.set_locale(Locale::DOT);
+        assert_eq!(evaluate("1,234.5 + max(1, 2)", &mut context), Ok(Answer::Number(Number::parse("1236.5", context.arithmetic()).unwrap())));
+        assert_eq!(evaluate("2,5", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation));
+
+        context.set_locale(Locale::COMMA);
+        assert_eq!(evaluate("1 234,5 * 2 + max(0,5, 1)", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("2470")));
+        assert_eq!(evaluate("2 3", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation));
+    }
+
+    #[test]
+    fn evaluate_in_base() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        for (message, result) in [
+            ("255 in hex", "0xFF"),
+            ("0xF0 + 0b1111 in bin", "0b11111111"),
+            ("8.5 in oct", "0o10.4"),
+            ("35 in base 36", "Z (base 36)"),
+            ("0xFF in dec", "255"),
+            ("-1 in hex", "-0x1")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+
+        context.set_format(Format { base: Some(Base { radix: 16, width: Some(16) }), ..Format::default() });
+        assert_eq!(evaluate("-1 in bin", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("0b1111111111111111")));
+        assert_eq!(evaluate("x = -2", &mut context), Ok(Answer::Number(Number::from(-2))));
+        assert_eq!(context.format().render(&Number::from(-2)), "0xFFFE");
+        assert_eq!(evaluate("2 in base 40", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::UnknownBase));
+
+        // Too big and too small numbers aren't converted, it would take too long
+        assert_eq!(evaluate("1e19700 in hex", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("1e19700")));
+        assert_eq!(evaluate("1e-19700 in bin", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("1e-19700")));
+    }
+
+    #[test]
+    fn evaluate_bitwise() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        for (message, result) in [
+            ("0xF0 | 0x0F in hex", "0xFF"),
+            ("0b1100 & 0b1010 in bin", "0b1000"),
+            ("0b1100 xor 0b1010 in bin", "0b110"),
+            ("~5", "-6"),
+            ("1 << 4", "16"),
+            ("0x80 >> 3", "16"),
+            ("1 << 2 + 1", "8"),
+            ("7 // 2", "3"),
+            ("-7 // 2", "-4"),
+            ("7.5 // 2", "3"),
+            ("x = 12", "12"),
+            ("x & 2x", "8"),
+            ("4.0 & 6", "4")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+
+        for message in ["1.5 & 1", "~0.5", "1 << -1", "2i | 1"] {
+            assert_eq!(evaluate(message, &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::DomainError), "{}", message);
+        }
+        assert_eq!(evaluate("5 // 0", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::DivisionByZero));
+        assert_eq!(evaluate("1 << 10^10", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::LimitExceeded));
+    }
+
+    #[test]
+    fn evaluate_implicit_multiplication() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        for (message, result) in [
+            ("2(3+4)", "14"),
+            ("(1+2)(3+4)", "21"),
+            ("(1+2)3", "9"),
+            ("x = 5", "5"),
+            ("2x", "10"),
+            ("2^3x", "40"),
+            ("-2x", "-10"),
+            ("1/2x", "2.5"),
+            ("2sqrt(16)", "8"),
+            ("3!x", "30"),
+            ("2i(1 + i)", "-2 + 2i"),
+            ("f(y) = 3y", "f"),
+            ("f(2)x", "30")
+        ] {
+            assert_eq!(evaluate(message, &mut context).map(|answer| answer_text(&answer)), Ok(String::from(result)), "{}", message);
+        }
+        assert!(matches!(evaluate("3pi", &mut context), Ok(Answer::Number(n)) if n.to_string().starts_with("9.42477796")));
+
+        context.set_strict(true);
+        assert_eq!(evaluate("2x", &mut context).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::MissedOperation, Some(Span { start: 1, end: 2 }))));
+        assert_eq!(evaluate("2 * x", &mut context), Ok(Answer::Number(Number::from(10))));
+    }
+
+    #[test]
+    fn evaluate_real_only() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("sqrt(-1)", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("i")));
+
+        context.set_real_only(true);
+        context.set_ieee(true);
+        for message in ["sqrt(-1)", "(-8)^(1/3)", "asin(2)", "floor(i)", "2i % 2"] {
+            assert!(matches!(evaluate(message, &mut context), Ok(Answer::Number(Number::Float(n))) if n.is_nan()), "{}", message);
+        }
+        assert_eq!(evaluate("i^2", &mut context), Ok(Answer::Number(Number::from(-1))));
+    }
+
+    #[test]
+    fn evaluate_evaluation_errors() {
+        use super::*;
+
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+        context.set_real_only(true);
+        for (message, error_type, span) in [
+            ("1 / 0", CalcErrorType::DivisionByZero, Span { start: 0, end: 5 }),
+            ("2 + 1 / (3 - 3)", CalcErrorType::DivisionByZero, Span { start: 4, end: 15 }),
+            ("0 / 0", CalcErrorType::DivisionByZero, Span { start: 0, end: 5 }),
+            ("5 % 0", CalcErrorType::DivisionByZero, Span { start: 0, end: 5 }),
+            ("0^-1", CalcErrorType::DivisionByZero, Span { start: 0, end: 4 }),
+            ("(-8)^(1/3)", CalcErrorType::DomainError, Span { start: 0, end: 10 }),
+            ("1 + sqrt(-1)", CalcErrorType::DomainError, Span { start: 4, end: 12 }),
+            ("ln(0)", CalcErrorType::DomainError, Span { start: 0, end: 5 }),
+            ("log(1, 5)", CalcErrorType::DomainError, Span { start: 0, end: 9 }),
+            ("log(0, 5)", CalcErrorType::DomainError, Span { start: 0, end: 9 }),
+            ("log(-2, 8)", CalcErrorType::DomainError, Span { start: 0, end: 10 }),
+            ("(-1)!", CalcErrorType::DomainError, Span { start: 0, end: 5 }),
+            ("inf - inf", CalcErrorType::DomainError, Span { start: 0, end: 9 }),
+            ("exp(1000)", CalcErrorType::Overflow, Span { start: 0, end: 9 }),
+            ("100000!", CalcErrorType::Overflow, Span { start: 0, end: 7 })
+        ] {
+            assert_eq!(evaluate(message, &mut context).map_err(|e| (e.error_type(), e.span())), Err((error_type, Some(span))), "{}", message);
+        }
+        assert_eq!(evaluate("inf + 1", &mut context), Ok(Answer::Number(Number::from(f64::INFINITY))));
+    }
+
+    #[test]
+    fn evaluate_ieee() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("1 / 0", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::DivisionByZero));
+
+        context.set_ieee(true);
+        assert_eq!(evaluate("1 / 0", &mut context), Ok(Answer::Number(Number::from(f64::INFINITY))));
+        assert_eq!(evaluate("exp(1000)", &mut context), Ok(Answer::Number(Number::from(f64::INFINITY))));
+        assert!(matches!(evaluate("0 / 0", &mut context), Ok(Answer::Number(Number::Float(n))) if n.is_nan()));
+    }
+
+    /// Sum of ones in RPN: 1 1 + 1 + ... or 1 1 ... 1 + + ..., when all operands go first
+    fn sum_of_ones(count: usize, operands_first: bool) -> Vec<RpnItem> {
+        let mut rpn = vec![operand(1.0)];
+        if operands_first {
+            rpn.extend(std::iter::repeat_n(operand(1.0), count - 1));
+            rpn.extend(std::iter::repeat_n(Oper::Add, count - 1));
+        }
+        else {
+            for _ in 1..count {
+                rpn.push(operand(1.0));
+                rpn.push(Oper::Add);
+            }
+        }
+        spanned(rpn)
+    }
+
+    #[test]
+    fn calculate_long_expression() {
+        use super::*;
+
+        assert_eq!(calculate(&sum_of_ones(50_000, false), &CalcContext::default()), Ok(Number::from(50_000)));
+        assert_eq!(calculate(&sum_of_ones(50_000, true), &CalcContext::default()), Ok(Number::from(50_000)));
+    }
+
+    /// Shows, that the time grows linearly with the length of the expression:
+    /// cargo test --release calculate_benchmark -- --ignored --nocapture
+    #[test]
+    #[ignore]
+    fn calculate_benchmark() {
+        use super::*;
+        use std::time::Instant;
+
+        let mut times = Vec::new();
+        for count in [12_500, 25_000, 50_000, 100_000] {
+            for operands_first in [false, true] {
+                let rpn = sum_of_ones(count / 2 + 1, operands_first);
+                let start = Instant::now();
+                assert!(calculate(&rpn, &CalcContext::default()).is_ok());
+                let time = start.elapsed();
+
+                println!("{} tokens, operands first: {}, {:?}", rpn.len(), operands_first, time);
+                times.push(time);
+            }
+        }
+
+        // 8 times more tokens take about 8 times more time, a quadratic algorithm would take 64 times more
+        assert!(times[6] < times[0] * 24);
+        assert!(times[7] < times[1] * 24);
+    }
+
+    #[test]
+    fn evaluate_limits() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        context.set_limits(Limits { max_input_length: 20, max_tokens: 15, max_nesting_depth: 4, max_exponent: 1000.0, max_steps: 10, ..Limits::default() });
+        for (message, span) in [
+            ("1 + 2 + 3 + 4 + 5 + 6 + 7", None),
+            ("1+1+1+1+1+1+1+1+1", Some(Span { start: 15, end: 16 })),
+            ("((((1))))", Some(Span { start: 4, end: 5 })),
+            ("2^1001", Some(Span { start: 0, end: 6 })),
+            ("9^9^9", Some(Span { start: 0, end: 5 }))
+        ] {
+            assert_eq!(evaluate(message, &mut context).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::LimitExceeded, span)), "{}", message);
+        }
+        assert!(evaluate("2^1000 + (1)", &mut context).is_ok());
+
+        // Every call calculates its body, so the steps of all calls are counted together
+        evaluate("f(x) = x + x", &mut context).unwrap();
+        evaluate("g(x) = f(f(f(x)))", &mut context).unwrap();
+        assert_eq!(evaluate("g(1)", &mut context), Ok(Answer::Number(Number::from(8))));
+        evaluate("h(x) = g(g(g(x)))", &mut context).unwrap();
+        assert_eq!(evaluate("h(1)", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::LimitExceeded));
+        assert_eq!(evaluate("g(1)", &mut context), Ok(Answer::Number(Number::from(8))));
+    }
+
+    #[test]
+    fn evaluate_huge_numbers_quickly() {
+        use super::*;
+        use std::time::{Duration, Instant};
+
+        for arithmetic in [Arithmetic::Decimal(DEFAULT_PRECISION), Arithmetic::Rational] {
+            let mut context = CalcContext::with_arithmetic(arithmetic);
+            for (message, error_type) in [
+                ("1 / 1e-999999", CalcErrorType::LimitExceeded),
+                ("1e999999 / 1e-999999", CalcErrorType::LimitExceeded),
+                ("1e999999^1000000", CalcErrorType::LimitExceeded),
+                ("(10^100000)^1000000", CalcErrorType::Overflow),
+                ("1e10000 / 1e-10000", CalcErrorType::Overflow)
+            ] {
+                let start = Instant::now();
+                assert_eq!(evaluate(message, &mut context).map_err(|e| e.error_type()), Err(error_type), "{} in {:?}", message, arithmetic);
+                assert!(start.elapsed() < Duration::from_secs(1), "{} in {:?}", message, arithmetic);
+            }
+        }
+
+        // Every operation with huge fractions takes long, so many of them are stopped by the time limit
+        let mut context = CalcContext::with_arithmetic(Arithmetic::Rational);
+        context.set_limits(Limits { max_time: Duration::from_millis(100), ..Limits::default() });
+        evaluate("x = 3^30000 / 7^20000", &mut context).unwrap();
+        let start = Instant::now();
+        assert_eq!(evaluate(&format!("{}x", "x * 2 / ".repeat(300)), &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::LimitExceeded));
+        assert!(start.elapsed() < Duration::from_secs(1));
+    }
+
+    #[test]
+    fn evaluate_default_limits_keep_stack() {
+        use super::*;
+
+        let limits = Limits::default();
+        for message in [
+            format!("{}1", "-".repeat(limits.max_nesting_depth - 1)),
+            format!("{}1{}", "(".repeat(limits.max_nesting_depth - 1), ")".repeat(limits.max_nesting_depth - 1)),
+            format!("{}1", "1^".repeat(limits.max_nesting_depth - 1)),
+            format!("{}1", "1 + ".repeat(limits.max_tokens / 2 - 1)),
+            format!("1{}", "!".repeat(limits.max_nesting_depth - 1))
+        ] {
+            assert!(evaluate(&message, &mut CalcContext::default()).is_ok(), "{}", message);
+        }
+
+        // Bodies of recursive functions are as deep as the limits allow, the calls add their depth up
+        let recursion = std::thread::Builder::new().stack_size(2 << 20).spawn(move || {
+            let mut context = CalcContext::default();
+            let minuses = "-".repeat(limits.max_nesting_depth / 2 - 2);
+            evaluate(&format!("f(x) = {}f({}x)", minuses, minuses), &mut context).unwrap();
+            evaluate("f(1)", &mut context).map_err(|e| e.error_type())
+        });
+        assert_eq!(recursion.unwrap().join().unwrap(), Err(CalcErrorType::RecursionTooDeep));
+    }
+
+    #[test]
+    fn evaluate_repeated_squares_overflow() {
+        use super::*;
+
+        let mut context = CalcContext::default();
+        assert_eq!(evaluate("f(x) = x * x", &mut context).map(|answer| answer_text(&answer)), Ok(String::from("f")));
+        assert_eq!(evaluate("f(f(f(f(f(f(f(f(f(f(f(2^32000))))))))))) % 10", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::Overflow));
+        assert!(evaluate("x = 2^32000", &mut context).is_ok());
+        assert!(evaluate("x = x * x", &mut context).is_ok());
+        assert_eq!(evaluate("x = x * x", &mut context).map_err(|e| e.error_type()), Err(CalcErrorType::Overflow));
+    }
+}
@@ -0,0 +1,815 @@
+use std::cmp::Ordering;
+use std::num::NonZeroU64;
+use std::str::FromStr;
+use bigdecimal::{BigDecimal, RoundingMode};
+use num_bigint::BigInt;
+use num_complex::Complex64;
+use num_integer::Integer;
+use num_rational::BigRational;
+use num_traits::{FromPrimitive, One, Signed, ToPrimitive, Zero};
+
+/// Significant digits of decimal numbers, if the user didn't choose another precision
+pub const DEFAULT_PRECISION: u64 = 50;
+/// Division of decimal numbers isn't more precise than this
+pub const MAX_PRECISION: u64 = 100;
+/// Integers are exact up to this size, bigger results are floats
+const MAX_INTEGER_BITS: u64 = 65_536;
+/// Decimals are exact up to this order of magnitude, about as big as the biggest integers. Bigger and smaller results are floats.
+const MAX_DECIMAL_EXPONENT: i64 = 19_728;
+
+/// How numbers are represented during a calculation. Integers are exact in any arithmetic,
+/// the arithmetic is about fractions: results of inexact division, fractional literals and so on.
+#[derive(Clone, Copy, Debug, Default, PartialEq)]
+pub enum Arithmetic {
+    /// Fast, but 0.1 + 0.2 = 0.30000000000000004
+    #[default]
+    Float,
+    /// Exact decimal fractions, results are rounded to this count of significant digits
+    Decimal(u64),
+    /// Exact fractions: 1/3 + 1/6 = 1/2. Irrational results of functions are floats.
+    Rational
+}
+
+/// Number in one of the representations.
+/// Infinity and NaN can't be decimal, so they are floats in any arithmetic.
+/// A rational number is never an integer, integers are always `Integer`.
+/// A complex number has a non-zero imaginary part, its parts are floats.
+#[derive(Clone, Debug)]
+pub enum Number {
+    Integer(BigInt),
+    Float(f64),
+    Decimal(BigDecimal),
+    Rational(BigRational),
+    Complex(Complex64)
+}
+
+impl Number {
+    /// Converts a literal like `12.5` to the representation of the arithmetic. Integer literals are integers.
+    pub fn parse(literal: &str, arithmetic: Arithmetic) -> Option<Number> {
+        if literal.chars().all(|ch| ch.is_ascii_digit()) {
+            return BigInt::from_str(literal).ok().map(Number::integer)
+        }
+
+        match arithmetic {
+            Arithmetic::Float => literal.parse().ok().map(Number::Float),
+            Arithmetic::Decimal(precision) => BigDecimal::from_str(literal).ok().map(|n| Number::decimal(n, precision)),
+            Arithmetic::Rational => BigDecimal::from_str(literal).ok().and_then(|n| decimal_out_of_limit(&n).or_else(|| decimal_to_rational(&n).map(Number::rational)))
+        }
+    }
+
+    /// Converts a float, e.g. a result of a function, to the representation of the arithmetic
+    pub fn from_f64(value: f64, arithmetic: Arithmetic) -> Number {
+        match arithmetic {
+            Arithmetic::Decimal(_) => Number::float_to_exact(value, arithmetic).unwrap_or(Number::Float(value)),
+            _ => Number::Float(value)
+        }
+    }
+
+    /// A decimal or a fraction with the value of the shortest text of a float: 0.1 instead of 0.1000000000000000055511151231257827.
+    /// Infinity and NaN can't be exact.
+    pub(super) fn float_to_exact(value: f64, arithmetic: Arithmetic) -> Option<Number> {
+        match arithmetic {
+            Arithmetic::Decimal(_) | Arithmetic::Rational if value.is_finite() => Number::parse(&format!("{:e}", value), arithmetic),
+            _ => None
+        }
+    }
+
+    /// Converts a number, e.g. a value of a variable, to the representation of the arithmetic
+    pub fn to_arithmetic(&self, arithmetic: Arithmetic) -> Number {
+        match (self, arithmetic) {
+            (Number::Integer(_) | Number::Complex(_), _) => self.clone(),
+            (Number::Float(n), _) => Number::from_f64(*n, arithmetic),
+            (_, Arithmetic::Float) => Number::Float(self.to_f64()),
+            (_, Arithmetic::Decimal(precision)) => self.to_decimal().map_or_else(|| self.clone(), |n| Number::decimal(n, precision)),
+            (_, Arithmetic::Rational) => self.to_rational().map_or_else(|| self.clone(), Number::rational)
+        }
+    }
+
+    pub fn to_f64(&self) -> f64 {
+        match self {
+            Number::Integer(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Float(n) => *n,
+            Number::Decimal(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Rational(n) => n.to_f64().unwrap_or(f64::NAN),
+            Number::Complex(_) => f64::NAN
+        }
+    }
+
+    pub fn to_complex(&self) -> Complex64 {
+        match self {
+            Number::Complex(n) => *n,
+            _ => Complex64::new(self.to_f64(), 0.0)
+        }
+    }
+
+    pub fn is_complex(&self) -> bool {
+        matches!(self, Number::Complex(_))
+    }
+
+    /// Infinity and NaN aren't finite, exact numbers are always finite
+    pub fn is_finite(&self) -> bool {
+        match self {
+            Number::Float(n) => n.is_finite(),
+            Number::Complex(n) => n.is_finite(),
+            _ => true
+        }
+    }
+
+    /// Checked without converting to a fraction, so it is cheap for any decimal
+    pub fn is_zero(&self) -> bool {
+        match self {
+            Number::Integer(n) => n.is_zero(),
+            Number::Float(n) => *n == 0.0,
+            Number::Decimal(n) => n.is_zero(),
+            Number::Rational(n) => n.is_zero(),
+            Number::Complex(n) => n.re == 0.0 && n.im == 0.0
+        }
+    }
+
+    /// Complex numbers are neither negative nor positive
+    pub fn is_negative(&self) -> bool {
+        match self {
+            Number::Integer(n) => n.is_negative(),
+            Number::Float(n) => *n < 0.0,
+            Number::Decimal(n) => n.is_negative(),
+            Number::Rational(n) => n.is_negative(),
+            Number::Complex(_) => false
+        }
+    }
+
+    pub fn is_nan(&self) -> bool {
+        match self {
+            Number::Float(n) => n.is_nan(),
+            Number::Complex(n) => n.is_nan(),
+            _ => false
+        }
+    }
+
+    /// Floats can't be converted exactly. Fractions like 1/3 are rounded to the default precision of decimals.
+    pub(super) fn to_decimal(&self) -> Option<BigDecimal> {
+        match self {
+            Number::Integer(n) => Some(BigDecimal::from(n.clone())),
+            Number::Float(_) | Number::Complex(_) => None,
+            Number::Decimal(n) => Some(n.clone()),
+            Number::Rational(n) => Some(BigDecimal::from(n.numer().clone()) / BigDecimal::from(n.denom().clone()))
+        }
+    }
+
+    /// Floats can't be converted exactly
+    pub(super) fn to_rational(&self) -> Option<BigRational> {
+        match self {
+            Number::Integer(n) => Some(BigRational::from_integer(n.clone())),
+            Number::Float(_) | Number::Complex(_) => None,
+            Number::Decimal(n) => decimal_to_rational(n),
+            Number::Rational(n) => Some(n.clone())
+        }
+    }
+
+    /// The exact fraction, if its numerator and denominator aren't bigger than integers can be.
+    /// The size of a decimal is checked before the conversion, so 1e999999 doesn't calculate 10^999999.
+    pub(super) fn to_limited_rational(&self) -> Option<BigRational> {
+        if let Number::Decimal(n) = self {
+            let (digits, scale) = n.as_bigint_and_exponent();
+            // A decimal digit is less than 10/3 bits
+            if digits.bits().saturating_add(scale.unsigned_abs().saturating_mul(10) / 3) > MAX_INTEGER_BITS { return None }
+        }
+
+        self.to_rational().filter(|n| n.numer().bits().max(n.denom().bits()) <= MAX_INTEGER_BITS)
+    }
+
+    /// Both operands as complex numbers, if one of them is complex
+    fn to_complexes(&self, other: &Number) -> Option<(Complex64, Complex64)> {
+        if self.is_complex() || other.is_complex() { Some((self.to_complex(), other.to_complex())) }
+        else { None }
+    }
+
+    /// Both operands as decimals, if the arithmetic is decimal and none of the operands is a float
+    fn to_decimals(&self, other: &Number, arithmetic: Arithmetic) -> Option<(BigDecimal, BigDecimal, u64)> {
+        match arithmetic {
+            Arithmetic::Decimal(precision) => Some((self.to_decimal()?, other.to_decimal()?, precision)),
+            _ => None
+        }
+    }
+
+    /// Both operands as fractions, if the arithmetic is rational and none of the operands is a float
+    fn to_rationals(&self, other: &Number, arithmetic: Arithmetic) -> Option<(BigRational, BigRational)> {
+        match arithmetic {
+            Arithmetic::Rational => Some((self.to_rational()?, other.to_rational()?)),
+            _ => None
+        }
+    }
+
+    pub fn add(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            return Number::integer(a + b)
+        }
+        if let Some((a, b)) = self.to_complexes(other) {
+            return Number::complex(a + b, arithmetic)
+        }
+        if let Some((a, b)) = self.to_rationals(other, arithmetic) {
+            return Number::rational(a + b)
+        }
+
+        match self.to_decimals(other, arithmetic) {
+            Some((a, b, precision)) => Number::decimal(a + b, precision),
+            None => Number::Float(self.to_f64() + other.to_f64())
+        }
+    }
+
+    pub fn sub(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            return Number::integer(a - b)
+        }
+        if let Some((a, b)) = self.to_complexes(other) {
+            return Number::complex(a - b, arithmetic)
+        }
+        if let Some((a, b)) = self.to_rationals(other, arithmetic) {
+            return Number::rational(a - b)
+        }
+
+        match self.to_decimals(other, arithmetic) {
+            Some((a, b, precision)) => Number::decimal(a - b, precision),
+            None => Number::Float(self.to_f64() - other.to_f64())
+        }
+    }
+
+    pub fn mul(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            return Number::integer(a * b)
+        }
+        if let Some((a, b)) = self.to_complexes(other) {
+            return Number::complex(a * b, arithmetic)
+        }
+        if let Some((a, b)) = self.to_rationals(other, arithmetic) {
+            return Number::rational(a * b)
+        }
+
+        match self.to_decimals(other, arithmetic) {
+            Some((a, b, precision)) => Number::decimal(a * b, precision),
+            None => Number::Float(self.to_f64() * other.to_f64())
+        }
+    }
+
+    /// Division of integers is exact, if the remainder is zero, otherwise the result is a fraction of the arithmetic
+    pub fn div(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            if !b.is_zero() && a.is_multiple_of(b) { return Number::Integer(a / b) }
+        }
+        if let Some((a, b)) = self.to_complexes(other) {
+            return Number::complex(a / b, arithmetic)
+        }
+        if let Some((a, b)) = self.to_rationals(other, arithmetic).filter(|(_, b)| !b.is_zero()) {
+            return Number::rational(a / b)
+        }
+
+        match self.to_decimals(other, arithmetic) {
+            Some((a, b, precision)) if !b.is_zero() => Number::decimal(a / b, precision),
+            _ => Number::Float(self.to_f64() / other.to_f64())
+        }
+    }
+
+    pub fn rem(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            if !b.is_zero() { return Number::Integer(a % b) }
+        }
+        if let Some((a, b)) = self.to_rationals(other, arithmetic).filter(|(_, b)| !b.is_zero()) {
+            return Number::rational(a % b)
+        }
+
+        match self.to_decimals(other, arithmetic) {
+            Some((a, b, precision)) if !b.is_zero() => Number::decimal(a % b, precision),
+            _ => Number::Float(self.to_f64() % other.to_f64())
+        }
+    }
+
+    /// Division rounded down: 7 // 2 = 3, -7 // 2 = -4. Integers are divided exactly, other numbers in the arithmetic.
+    pub fn int_div(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let (Some(a), Some(b)) = (self.integer_value(), other.integer_value()) {
+            if !b.is_zero() { return Number::Integer(a.div_floor(&b)) }
+        }
+
+        let quotient = self.div(other, arithmetic);
+        quotient.to_integer(RoundingMode::Floor).unwrap_or_else(|| Number::Float(quotient.to_f64().floor()))
+    }
+
+    /// Non-negative integer powers of integers are exact, if the result isn't too big.
+    /// Integer powers of fractions are exact too, decimals are rounded to the precision.
+    /// Other powers are calculated with floats. Integer powers of complex numbers are products, so i^2 is exactly -1.
+    pub fn pow(&self, other: &Number, arithmetic: Arithmetic) -> Number {
+        if let Some((a, b)) = self.to_complexes(other) {
+            let exponent = other.to_f64();
+            let value = if exponent.fract() == 0.0 && exponent.abs() <= f64::from(i32::MAX) { a.powi(exponent as i32) } else { a.powc(b) };
+            return Number::complex(value, arithmetic)
+        }
+        // Zero in a negative power is infinity, it can't be exact
+        if self.is_zero() && other.is_negative() { return Number::Float(f64::INFINITY) }
+
+        if let (Number::Integer(a), Number::Integer(b)) = (self, other) {
+            if let Some(exponent) = b.to_u32() {
+                if a.bits().saturating_mul(u64::from(exponent)) <= MAX_INTEGER_BITS || a.abs().is_one() || a.is_zero() {
+                    return Number::Integer(a.pow(exponent))
+                }
+            }
+        }
+
+        if let (Some((a, _)), Number::Integer(exponent)) = (self.to_rationals(other, arithmetic), other) {
+            let bits = a.numer().bits().max(a.denom().bits());
+            if let Some(exponent) = exponent.to_i32() {
+                if bits.saturating_mul(u64::from(exponent.unsigned_abs())) <= MAX_INTEGER_BITS {
+                    return Number::rational(a.pow(exponent))
+                }
+            }
+        }
+
+        if let Some((a, b, precision)) = self.to_decimals(other, arithmetic) {
+            if b.is_integer() {
+                // The order of magnitude of the power is checked before it is calculated, like the size of an integer power
+                let (digits, scale) = a.as_bigint_and_exponent();
+                let magnitude = a.order_of_magnitude();
+                let log10 = BigDecimal::new(digits, scale + magnitude).to_f64().map_or(f64::NAN, |mantissa| mantissa.abs().log10()) + magnitude as f64;
+                if let Some(exponent) = b.to_i64().filter(|exponent| (log10 * *exponent as f64).abs() <= MAX_DECIMAL_EXPONENT as f64) {
+                    let context = bigdecimal::Context::new(NonZeroU64::new(precision.max(1)).unwrap(), RoundingMode::HalfEven);
+                    return Number::decimal(a.powi_with_context(exponent, &context), precision)
+                }
+            }
+        }
+
+        Number::from_f64(self.to_f64().powf(other.to_f64()), arithmetic)
+    }
+
+    pub fn neg(&self) -> Number {
+        match self {
+            Number::Integer(n) => Number::Integer(-n),
+            Number::Float(n) => Number::Float(-n),
+            Number::Decimal(n) => Number::Decimal(-n),
+            Number::Rational(n) => Number::Rational(-n),
+            Number::Complex(n) => Number::Complex(-n)
+        }
+    }
+
+    /// Factorial of a non-negative integer is an exact integer, if it isn't too big
+    pub fn factorial(&self) -> Number {
+        let n = match self.integer_value().and_then(|n| n.to_u64()) {
+            Some(n) => n,
+            None => return Number::Float(f64::NAN)
+        };
+
+        let mut result = BigInt::one();
+        for i in 2..=n {
+            result *= i;
+            if result.bits() > MAX_INTEGER_BITS { return Number::Float(f64::INFINITY) }
+        }
+        Number::Integer(result)
+    }
+
+    /// Bitwise operations are defined only for integers, negative ones are in two's complement: -1 & 6 = 6
+    pub fn bit_and(&self, other: &Number) -> Number {
+        self.bitwise(other, |a, b| a & b)
+    }
+
+    pub fn bit_or(&self, other: &Number) -> Number {
+        self.bitwise(other, |a, b| a | b)
+    }
+
+    pub fn bit_xor(&self, other: &Number) -> Number {
+        self.bitwise(other, |a, b| a ^ b)
+    }
+
+    /// ~5 = -6
+    pub fn bit_not(&self) -> Number {
+        self.integer_value().map_or(Number::Float(f64::NAN), |n| Number::Integer(!n))
+    }
+
+    /// Shift by a negative count isn't defined. A too big result is infinity, like a too big power.
+    pub fn shl(&self, other: &Number) -> Number {
+        match (self.integer_value(), other.integer_value().and_then(|shift| shift.to_u64())) {
+            (Some(n), Some(shift)) if !n.is_zero() && n.bits() + shift > MAX_INTEGER_BITS => {
+                Number::Float(if n.is_negative() { f64::NEG_INFINITY } else { f64::INFINITY })
+            },
+            (Some(n), Some(shift)) => Number::Integer(n << shift),
+            _ => Number::Float(f64::NAN)
+        }
+    }
+
+    /// Shift of a negative integer is rounded down: -5 >> 1 = -3
+    pub fn shr(&self, other: &Number) -> Number {
+        match (self.integer_value(), other.integer_value().and_then(|shift| shift.to_u64())) {
+            (Some(n), Some(shift)) => Number::Integer(n >> shift),
+            _ => Number::Float(f64::NAN)
+        }
+    }
+
+    fn bitwise(&self, other: &Number, operation: impl Fn(&BigInt, &BigInt) -> BigInt) -> Number {
+        match (self.integer_value(), other.integer_value()) {
+            (Some(a), Some(b)) => Number::Integer(operation(&a, &b)),
+            _ => Number::Float(f64::NAN)
+        }
+    }
+
+    /// The value of a number without a fractional part in any representation: 4, 4.0
+    fn integer_value(&self) -> Option<BigInt> {
+        match self {
+            Number::Integer(n) => Some(n.clone()),
+            Number::Float(n) if n.fract() == 0.0 => BigInt::from_f64(*n),
+            Number::Decimal(n) if n.is_integer() => Some(n.with_scale(0).as_bigint_and_exponent().0),
+            _ => None
+        }
+    }
+
+    pub fn abs(&self) -> Number {
+        match self {
+            Number::Integer(n) => Number::Integer(n.abs()),
+            Number::Float(n) => Number::Float(n.abs()),
+            Number::Decimal(n) => Number::Decimal(n.abs()),
+            Number::Rational(n) => Number::Rational(n.abs()),
+            Number::Complex(n) => Number::Float(n.norm())
+        }
+    }
+
+    /// Rounds an exact number to an integer, floats are rounded by themselves.
+    /// Only floor, ceiling and half up (away from zero) modes are used.
+    pub fn to_integer(&self, mode: RoundingMode) -> Option<Number> {
+        match self {
+            Number::Integer(_) => Some(self.clone()),
+            Number::Float(_) | Number::Complex(_) => None,
+            Number::Decimal(n) => Some(Number::Integer(n.with_scale_round(0, mode).as_bigint_and_exponent().0)),
+            Number::Rational(n) => {
+                let rounded = match mode {
+                    RoundingMode::Floor => n.floor(),
+                    RoundingMode::Ceiling => n.ceil(),
+                    _ => n.round()
+                };
+                Some(Number::Integer(rounded.to_integer()))
+            }
+        }
+    }
+
+    /// Rounds a decimal to the precision and drops trailing zeros.
+    /// A decimal out of the limit is a float, like a too big integer: infinity or a tiny float.
+    fn decimal(value: BigDecimal, precision: u64) -> Number {
+        if let Some(n) = decimal_out_of_limit(&value) { return n }
+
+        let value = if value.digits() > precision { value.with_prec(precision) } else { value };
+        Number::Decimal(value.normalized())
+    }
+
+    /// A complex number without the imaginary part is a real number of the arithmetic
+    pub fn complex(value: Complex64, arithmetic: Arithmetic) -> Number {
+        if value.im == 0.0 { Number::from_f64(value.re, arithmetic) }
+        else { Number::Complex(value) }
+    }
+
+    /// An integer bigger than the limit is a float, so repeated operations can't make it grow endlessly
+    fn integer(value: BigInt) -> Number {
+        if value.bits() <= MAX_INTEGER_BITS { Number::Integer(value) }
+        else { Number::Float(value.to_f64().unwrap_or(f64::NAN)) }
+    }
+
+    /// A fraction with the denominator 1 is an integer. A fraction with too big parts is a float, like a too big integer.
+    fn rational(value: BigRational) -> Number {
+        if value.is_integer() { Number::integer(value.to_integer()) }
+        else if value.numer().bits().max(value.denom().bits()) > MAX_INTEGER_BITS { Number::Float(value.to_f64().unwrap_or(f64::NAN)) }
+        else { Number::Rational(value) }
+    }
+}
+
+/// A float instead of a decimal, which is too big or too small to be exact
+fn decimal_out_of_limit(value: &BigDecimal) -> Option<Number> {
+    (value.order_of_magnitude().abs() > MAX_DECIMAL_EXPONENT).then(|| Number::Float(value.to_f64().unwrap_or(f64::NAN)))
+}
+
+/// The power of 10 is calculated, so it is expensive for decimals with a big scale. None, if the scale is too big for it.
+fn decimal_to_rational(value: &BigDecimal) -> Option<BigRational> {
+    let (digits, scale) = value.as_bigint_and_exponent();
+    let power = BigInt::from(10).pow(u32::try_from(scale.unsigned_abs()).ok()?);
+
+    if scale >= 0 { Some(BigRational::new(digits, power)) }
+    else { Some(BigRational::from_integer(digits * power)) }
+}
+
+impl From<f64> for Number {
+    fn from(value: f64) -> Number {
+        Number::Float(value)
+    }
+}
+
+impl From<i64> for Number {
+    fn from(value: i64) -> Number {
+        Number::Integer(BigInt::from(value))
+    }
+}
+
+/// Numbers are equal, if their values are equal, whatever the representations are
+impl PartialEq for Number {
+    fn eq(&self, other: &Number) -> bool {
+        if let Some((a, b)) = self.to_complexes(other) { return a == b }
+
+        match (self.to_rational(), other.to_rational()) {
+            (Some(a), Some(b)) => a == b,
+            _ => self.to_f64() == other.to_f64()
+        }
+    }
+}
+
+impl PartialOrd for Number {
+    fn partial_cmp(&self, other: &Number) -> Option<Ordering> {
+        // Complex numbers aren't ordered
+        if self.to_complexes(other).is_some() { return (self == other).then_some(Ordering::Equal) }
+
+        match (self.to_rational(), other.to_rational()) {
+            (Some(a), Some(b)) => a.partial_cmp(&b),
+            _ => self.to_f64().partial_cmp(&other.to_f64())
+        }
+    }
+}
+
+impl std::fmt::Display for Number {
+    fn fmt(&self, f: &mut std::fmt::Formatter) -> std::fmt::Result {
+        match self {
+            Number::Integer(n) => write!(f, "{}", n),
+            Number::Float(n) => write!(f, "{}", n),
+            // Decimals have no more significant digits than the maximal precision, so a longer plain text is mostly zeros
+            Number::Decimal(n) if n.order_of_magnitude().unsigned_abs() > MAX_PRECISION => write!(f, "{}", n.to_scientific_notation()),
+            Number::Decimal(n) => write!(f, "{}", n.to_plain_string()),
+            Number::Rational(n) => write!(f, "{}/{}", n.numer(), n.denom()),
+            Number::Complex(n) => write!(f, "{}", complex_to_string(*n, |part| part.to_string()))
+        }
+    }
+}
+
+/// Writes a complex number like 2 - 3i, the parts are written by the function
+pub(super) fn complex_to_string(value: Complex64, part_to_string: impl Fn(f64) -> String) -> String {
+    // The coefficient 1 isn't shown: 2 - i, not 2 - 1i
+    let im = if value.im.abs() == 1.0 { String::new() } else { part_to_string(value.im.abs()) };
+    match (value.re == 0.0, value.im < 0.0) {
+        (true, false) => format!("{}i", im),
+        (true, true) => format!("-{}i", im),
+        (false, false) => format!("{} + {}i", part_to_string(value.re), im),
+        (false, true) => format!("{} - {}i", part_to_string(value.re), im)
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    #[test]
+    fn parse_literals() {
+        use super::*;
+
+        assert_eq!(Number::parse("0.1", Arithmetic::Float), Some(Number::Float(0.1)));
+        assert_eq!(Number::parse("1.2.3", Arithmetic::Float), None);
+
+        let long = "123456789012345678901234567890";
+        assert_eq!(Number::parse(long, Arithmetic::Decimal(DEFAULT_PRECISION)).map(|n| n.to_string()), Some(String::from(long)));
+        assert_eq!(Number::parse(".5", Arithmetic::Decimal(DEFAULT_PRECISION)).map(|n| n.to_string()), Some(String::from("0.5")));
+    }
+
+    #[test]
+    fn decimal_arithmetic_is_exact() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Decimal(DEFAULT_PRECISION);
+        let a = Number::parse("0.1", arithmetic).unwrap();
+        let b = Number::parse("0.2", arithmetic).unwrap();
+        assert_eq!(a.add(&b, arithmetic).to_string(), "0.3");
+        assert_eq!(b.sub(&a, arithmetic).to_string(), "0.1");
+        assert_eq!(a.mul(&b, arithmetic).to_string(), "0.02");
+        assert_eq!(a.div(&b, arithmetic).to_string(), "0.5");
+        assert_eq!(Number::parse("0.7", arithmetic).unwrap().rem(&b, arithmetic).to_string(), "0.1");
+        assert_eq!(Number::parse("1.1", arithmetic).unwrap().pow(&Number::parse("2", arithmetic).unwrap(), arithmetic).to_string(), "1.21");
+    }
+
+    #[test]
+    fn decimal_powers_are_limited() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Decimal(DEFAULT_PRECISION);
+        let power = |base: &str, exponent: i64| Number::parse(base, arithmetic).unwrap().pow(&Number::from(exponent), arithmetic);
+        assert_eq!(power("1e19000", 1_000_000), Number::Float(f64::INFINITY));
+        assert_eq!(power("-1e-19000", 999_999), Number::Float(-0.0));
+        assert!(matches!(power("1.0001", 100_000), Number::Decimal(_)));
+        assert_eq!(power("1e100", 2).to_string(), "1e200");
+        assert_eq!(power("1.5e-60", 2).to_string(), "2.25e-120");
+        assert_eq!(power("1e50", 2).to_string(), format!("1{}", "0".repeat(100)));
+
+        let zero = Number::parse("0.0", Arithmetic::Rational).unwrap();
+        assert_eq!(power("0.0", -1), Number::Float(f64::INFINITY));
+        assert_eq!(zero.pow(&Number::from(-2), Arithmetic::Rational), Number::Float(f64::INFINITY));
+    }
+
+    #[test]
+    fn decimal_precision() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Decimal(5);
+        let one = Number::parse("1", arithmetic).unwrap();
+        let three = Number::parse("3", arithmetic).unwrap();
+        assert_eq!(one.div(&three, arithmetic).to_string(), "0.33333");
+        assert_eq!(Number::parse("123456.789", arithmetic).unwrap().to_string(), "123460");
+    }
+
+    #[test]
+    fn decimal_without_decimal_result() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Decimal(DEFAULT_PRECISION);
+        let zero = Number::parse("0", arithmetic).unwrap();
+        let two = Number::parse("2", arithmetic).unwrap();
+        assert_eq!(two.div(&zero, arithmetic), Number::Float(f64::INFINITY));
+        assert_eq!(zero.pow(&Number::parse("-1", arithmetic).unwrap(), arithmetic), Number::Float(f64::INFINITY));
+        assert_eq!(Number::from_f64(f64::NEG_INFINITY, arithmetic), Number::Float(f64::NEG_INFINITY));
+        assert_eq!(Number::from_f64(0.1, arithmetic).to_string(), "0.1");
+    }
+
+    #[test]
+    fn integer_arithmetic_is_exact() {
+        use super::*;
+
+        let big = Number::parse("123456789012345678901234567890", Arithmetic::Float).unwrap();
+        assert_eq!(big.mul(&Number::from(3), Arithmetic::Float).to_string(), "370370367037037036703703703670");
+        assert_eq!(big.add(&Number::from(10), Arithmetic::Float).to_string(), "123456789012345678901234567900");
+        assert_eq!(big.sub(&big, Arithmetic::Float).to_string(), "0");
+        assert_eq!(big.div(&Number::from(10), Arithmetic::Float).to_string(), "12345678901234567890123456789");
+        assert_eq!(big.rem(&Number::from(11), Arithmetic::Float).to_string(), "7");
+        assert_eq!(Number::from(-7).rem(&Number::from(2), Arithmetic::Float), Number::from(-1));
+        assert_eq!(Number::from(2).pow(&Number::from(200), Arithmetic::Float).to_string(), "1606938044258990275541962092341162602522202993782792835301376");
+    }
+
+    #[test]
+    fn integer_division_falls_back_to_arithmetic() {
+        use super::*;
+
+        assert_eq!(Number::from(7).div(&Number::from(2), Arithmetic::Float), Number::Float(3.5));
+        assert_eq!(Number::from(1).div(&Number::from(3), Arithmetic::Decimal(5)).to_string(), "0.33333");
+        assert_eq!(Number::from(1).div(&Number::from(0), Arithmetic::Float), Number::Float(f64::INFINITY));
+        assert_eq!(Number::from(2).pow(&Number::from(-1), Arithmetic::Float), Number::Float(0.5));
+        assert_eq!(Number::from(2).pow(&Number::from(-1), Arithmetic::Decimal(5)).to_string(), "0.5");
+        assert_eq!(Number::from(10).pow(&Number::from(100_000), Arithmetic::Float), Number::Float(f64::INFINITY));
+        assert_eq!(Number::from(-1).pow(&Number::from(100_001), Arithmetic::Float), Number::from(-1));
+    }
+
+    #[test]
+    fn factorial() {
+        use super::*;
+
+        assert_eq!(Number::from(0).factorial(), Number::from(1));
+        assert_eq!(Number::from(5).factorial(), Number::from(120));
+        assert_eq!(Number::from(5.0).factorial(), Number::from(120));
+        assert_eq!(Number::from(50).factorial().to_string(), "30414093201713378043612608166064768844377641568960512000000000000");
+        assert!(Number::from(-1).factorial().to_f64().is_nan());
+        assert!(Number::from(2.5).factorial().to_f64().is_nan());
+        assert_eq!(Number::from(100_000).factorial(), Number::Float(f64::INFINITY));
+    }
+
+    #[test]
+    fn bitwise() {
+        use super::*;
+
+        assert_eq!(Number::from(12).bit_and(&Number::from(10)), Number::from(8));
+        assert_eq!(Number::from(12).bit_or(&Number::from(10.0)), Number::from(14));
+        assert_eq!(Number::from(12).bit_xor(&Number::from(10)), Number::from(6));
+        assert_eq!(Number::from(-1).bit_and(&Number::from(6)), Number::from(6));
+        assert_eq!(Number::from(5).bit_not(), Number::from(-6));
+        assert_eq!(Number::from(1).shl(&Number::from(100)).to_string(), "1267650600228229401496703205376");
+        assert_eq!(Number::from(-5).shr(&Number::from(1)), Number::from(-3));
+        assert!(Number::from(2.5).bit_and(&Number::from(1)).to_f64().is_nan());
+        assert!(Number::from(1).shl(&Number::from(-1)).to_f64().is_nan());
+        assert_eq!(Number::from(1).shl(&Number::from(100_000)), Number::Float(f64::INFINITY));
+        assert_eq!(Number::from(0).shl(&Number::from(100_000)), Number::from(0));
+    }
+
+    #[test]
+    fn int_div() {
+        use super::*;
+
+        assert_eq!(Number::from(7).int_div(&Number::from(2), Arithmetic::Float), Number::from(3));
+        assert_eq!(Number::from(-7).int_div(&Number::from(2), Arithmetic::Float), Number::from(-4));
+        assert_eq!(Number::from(7.5).int_div(&Number::from(2), Arithmetic::Decimal(DEFAULT_PRECISION)), Number::from(3));
+        assert_eq!(Number::from(7.5).int_div(&Number::from(-2), Arithmetic::Float), Number::from(-4.0));
+        assert!(Number::from(1).int_div(&Number::from(0), Arithmetic::Float).to_f64().is_infinite());
+    }
+
+    #[test]
+    fn integers_are_limited() {
+        use super::*;
+
+        let big = Number::from(1).shl(&Number::from(40_000));
+        assert_eq!(big.mul(&big, Arithmetic::Float), Number::Float(f64::INFINITY));
+        assert_eq!(big.neg().mul(&big, Arithmetic::Rational), Number::Float(f64::NEG_INFINITY));
+        let biggest = Number::from(1).shl(&Number::from(65_535));
+        assert!(matches!(biggest.add(&Number::from(1), Arithmetic::Float), Number::Integer(_)));
+        assert_eq!(biggest.add(&biggest, Arithmetic::Float), Number::Float(f64::INFINITY));
+        assert_eq!(biggest.neg().sub(&biggest, Arithmetic::Float), Number::Float(f64::NEG_INFINITY));
+
+        let fraction = big.div(&Number::from(3), Arithmetic::Rational);
+        assert!(matches!(fraction, Number::Rational(_)));
+        assert_eq!(fraction.mul(&fraction, Arithmetic::Rational), Number::Float(f64::INFINITY));
+
+        let arithmetic = Arithmetic::Decimal(DEFAULT_PRECISION);
+        let decimal = Number::parse("1.5e10000", arithmetic).unwrap();
+        assert!(matches!(decimal, Number::Decimal(_)));
+        assert_eq!(decimal.mul(&decimal, arithmetic), Number::Float(f64::INFINITY));
+        assert_eq!(decimal.neg().add(&Number::parse("-1e19728", arithmetic).unwrap(), arithmetic), Number::Float(f64::NEG_INFINITY));
+        assert_eq!(Number::from(1).div(&decimal.mul(&Number::parse("1e9800", arithmetic).unwrap(), arithmetic), arithmetic), Number::Float(0.0));
+        assert_eq!(Number::parse("1e30000", arithmetic), Some(Number::Float(f64::INFINITY)));
+        assert_eq!(Number::parse(&format!("1{}", "0".repeat(20_000)), arithmetic), Some(Number::Float(f64::INFINITY)));
+    }
+
+    #[test]
+    fn rational_arithmetic_is_exact() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Rational;
+        let third = Number::from(1).div(&Number::from(3), arithmetic);
+        let sixth = Number::from(1).div(&Number::from(6), arithmetic);
+        assert_eq!(third.to_string(), "1/3");
+        assert_eq!(third.add(&sixth, arithmetic).to_string(), "1/2");
+        assert_eq!(third.sub(&sixth, arithmetic).to_string(), "1/6");
+        assert_eq!(third.mul(&Number::from(3), arithmetic), Number::from(1));
+        assert_eq!(third.div(&sixth, arithmetic), Number::from(2));
+        assert_eq!(Number::from(7).div(&Number::from(3), arithmetic).rem(&Number::from(1), arithmetic).to_string(), "1/3");
+        assert_eq!(third.pow(&Number::from(-2), arithmetic), Number::from(9));
+        assert_eq!(Number::from(2).pow(&Number::from(-3), arithmetic).to_string(), "1/8");
+        assert_eq!(Number::parse("0.25", arithmetic).map(|n| n.to_string()), Some(String::from("1/4")));
+    }
+
+    #[test]
+    fn rational_without_rational_result() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Rational;
+        assert_eq!(Number::from(1).div(&Number::from(0), arithmetic), Number::Float(f64::INFINITY));
+        assert_eq!(Number::from(0).pow(&Number::from(-1), arithmetic), Number::Float(f64::INFINITY));
+        assert_eq!(Number::from(4).pow(&Number::from(1).div(&Number::from(2), arithmetic), arithmetic), Number::Float(2.0));
+        assert_eq!(Number::from_f64(0.5, arithmetic), Number::Float(0.5));
+    }
+
+    #[test]
+    fn rational_to_other_arithmetic() {
+        use super::*;
+
+        let third = Number::from(1).div(&Number::from(3), Arithmetic::Rational);
+        assert_eq!(third.to_arithmetic(Arithmetic::Decimal(3)).to_string(), "0.333");
+        assert_eq!(third.to_arithmetic(Arithmetic::Float), Number::Float(1.0 / 3.0));
+        assert_eq!(Number::parse("0.2", Arithmetic::Decimal(3)).unwrap().to_arithmetic(Arithmetic::Rational).to_string(), "1/5");
+        assert_eq!(third.to_integer(RoundingMode::Ceiling), Some(Number::from(1)));
+        assert!(third < Number::from(0.5));
+    }
+
+    #[test]
+    fn float_arithmetic() {
+        use super::*;
+
+        let a = Number::from(0.1);
+        let b = Number::from(0.2);
+        assert_eq!(a.add(&b, Arithmetic::Float), Number::Float(0.30000000000000004));
+        assert_eq!(Number::from(2.0).pow(&Number::from(0.5), Arithmetic::Float), Number::Float(2f64.sqrt()));
+    }
+
+    #[test]
+    fn complex_arithmetic() {
+        use super::*;
+
+        let arithmetic = Arithmetic::Float;
+        let a = Number::Complex(Complex64::new(3.0, 4.0));
+        let b = Number::Complex(Complex64::new(1.0, -2.0));
+        assert_eq!(a.mul(&b, arithmetic), Number::Complex(Complex64::new(11.0, -2.0)));
+        assert_eq!(a.add(&b.neg(), arithmetic), Number::Complex(Complex64::new(2.0, 6.0)));
+        assert_eq!(a.sub(&Number::Complex(Complex64::new(0.0, 4.0)), arithmetic), Number::from(3));
+        assert_eq!(Number::Complex(Complex64::i()).pow(&Number::from(2), arithmetic), Number::from(-1));
+        assert_eq!(a.abs(), Number::from(5));
+        assert!(a.rem(&Number::from(2), arithmetic).is_nan());
+        assert!(a.factorial().is_nan());
+        assert_eq!(a.partial_cmp(&b), None);
+    }
+
+    #[test]
+    fn complex_to_string() {
+        use super::*;
+
+        assert_eq!(Number::Complex(Complex64::new(2.0, -3.0)).to_string(), "2 - 3i");
+        assert_eq!(Number::Complex(Complex64::new(0.5, 1.0)).to_string(), "0.5 + i");
+        assert_eq!(Number::Complex(Complex64::new(0.0, 2.5)).to_string(), "2.5i");
+        assert_eq!(Number::Complex(Complex64::new(0.0, -1.0)).to_string(), "-i");
+        assert_eq!(Number::complex(Complex64::new(0.5, 0.0), Arithmetic::Decimal(DEFAULT_PRECISION)).to_string(), "0.5");
+    }
+
+    #[test]
+    fn zero_and_sign() {
+        use super::*;
+
+        let huge_scale = Number::Decimal(BigDecimal::new(BigInt::from(-5), i64::from(u32::MAX) + 1));
+        assert!(huge_scale.is_negative() && !huge_scale.is_zero());
+        assert_eq!(huge_scale.to_rational(), None);
+        assert!(Number::Decimal(BigDecimal::zero()).is_zero());
+        assert!(Number::from(-0.0).is_zero() && !Number::from(-0.0).is_negative());
+        assert!(!Number::Complex(Complex64::new(-1.0, 1.0)).is_negative());
+    }
+
+    #[test]
+    fn equal_in_different_representations() {
+        use super::*;
+
+        assert_eq!(Number::parse("2.5", Arithmetic::Decimal(DEFAULT_PRECISION)), Some(Number::Float(2.5)));
+        assert_eq!(Number::Float(2.5).to_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION)).to_string(), "2.5");
+    }
+}
@@ -0,0 +1,817 @@
+use super::constants::find_constant;
+use super::context::{Context, UserFunction};
+use super::functions::Function;
+use super::lexer::{Span, Token, TokenKind};
+use num_complex::Complex64;
+use super::number::{Arithmetic, Number};
+use super::{calculate, CalcError, CalcErrorType, Oper, RpnItem};
+
+/// How many user functions can be called one inside another
+const MAX_CALL_DEPTH: usize = 64;
+/// How deep an expression can be with bodies of called functions in place of calls.
+/// Converting to RPN goes deeper into the stack with every level, and a body adds its levels to the levels of the call.
+const MAX_EXPANDED_DEPTH: usize = 500;
+
+/// What the user asks to do with an expression
+#[derive(Clone, Debug, PartialEq)]
+pub enum Statement {
+    Expression(Expr),
+    Assignment(String, Expr),
+    /// Function name, names of parameters and the body
+    Definition(String, Vec<String>, Expr),
+    /// Expression, which result is shown in the base: `255 in hex`, `100 in base 36`
+    Conversion(Expr, u32)
+}
+
+/// Names, which an expression can refer to
+pub struct Scope<'a> {
+    context: &'a Context,
+    /// Values of parameters, when the expression is a body of a user function
+    arguments: Vec<(String, Number)>,
+    /// How many user functions are called one inside another
+    depth: usize,
+    /// How deep the calls are in expressions, the bodies of the called functions included
+    nesting: usize
+}
+
+impl<'a> Scope<'a> {
+    pub fn new(context: &'a Context) -> Scope<'a> {
+        Scope { context, arguments: Vec::new(), depth: 0, nesting: 0 }
+    }
+
+    /// Parameters shadow variables, variables shadow the previous result, it shadows constants.
+    /// The imaginary unit `i` is the last, so `i` can still be a usual variable.
+    /// The value is converted to the arithmetic of the context.
+    fn get_value(&self, name: &str) -> Option<Number> {
+        let arithmetic = self.context.arithmetic();
+
+        self.arguments.iter().find(|(parameter, _)| parameter == name).map(|(_, value)| value)
+            .or_else(|| self.context.get_variable(name))
+            .or_else(|| if name == "ans" || name == "_" { self.context.get_last_result() } else { None })
+            .map(|value| value.to_arithmetic(arithmetic))
+            .or_else(|| find_constant(name).map(|constant| Number::from_f64(constant.value, arithmetic)))
+            .or_else(|| if name == "i" { Some(Number::Complex(Complex64::i())) } else { None })
+    }
+}
+
+#[derive(Clone, Debug, PartialEq)]
+pub enum ExprKind {
+    /// Text of a number, it is converted, when the arithmetic is known
+    Number(String),
+    /// Text of the coefficient of an imaginary number: 4 for 4i
+    Imaginary(String),
+    Name(String),
+    Result(usize),
+    Unary(Oper, Box<Expr>),
+    Binary(Oper, Box<Expr>, Box<Expr>),
+    Call(String, Vec<Expr>)
+}
+
+/// Node of an expression tree. The span covers the whole sub-expression, brackets included.
+#[derive(Clone, Debug, PartialEq)]
+pub struct Expr {
+    pub kind: ExprKind,
+    pub span: Span
+}
+
+impl Expr {
+    /// Writes the tree to the stack in RPN: operands first, then the operation.
+    /// Every item keeps the span of the sub-expression it stands for.
+    /// Names are resolved here, so an unknown name or a wrong call is an error.
+    /// User functions are calculated here too, their results get into RPN as operands.
+    pub fn to_rpn(&self, rpn: &mut Vec<RpnItem>, scope: &Scope) -> Result<(), CalcError> {
+        self.to_rpn_nested(rpn, scope, scope.nesting)
+    }
+
+    fn to_rpn_nested(&self, rpn: &mut Vec<RpnItem>, scope: &Scope, nesting: usize) -> Result<(), CalcError> {
+        if nesting >= MAX_EXPANDED_DEPTH {
+            let error_type = if scope.depth > 0 { CalcErrorType::RecursionTooDeep } else { CalcErrorType::LimitExceeded };
+            return Err(CalcError::with_span(error_type, self.span))
+        }
+
+        let oper = match &self.kind {
+            ExprKind::Number(literal) => {
+                // 1e100000000 has as many digits as 10^100000000
+                let exponent = literal.split_once('e').and_then(|(_, exponent)| exponent.parse::<f64>().ok());
+                if exponent.is_some_and(|exponent| exponent.abs() > scope.context.limits().max_exponent) {
+                    return Err(CalcError::with_span(CalcErrorType::LimitExceeded, self.span))
+                }
+
+                let arithmetic = scope.context.arithmetic();
+                match Number::parse(literal, arithmetic) {
+                    // A literal out of the limits of exact numbers, like 1e-999999, would be infinity or zero
+                    Some(value) if !value.is_finite() || (matches!(value, Number::Float(_)) && arithmetic != Arithmetic::Float) => {
+                        return Err(CalcError::with_span(CalcErrorType::LimitExceeded, self.span))
+                    },
+                    Some(value) => Oper::Operand(value),
+                    None => return Err(CalcError::with_span(CalcErrorType::OperandNotNumber, self.span))
+                }
+            },
+            ExprKind::Imaginary(literal) => {
+                match literal.parse() {
+                    Ok(value) => Oper::Operand(Number::complex(Complex64::new(0.0, value), scope.context.arithmetic())),
+                    Err(_) => return Err(CalcError::with_span(CalcErrorType::OperandNotNumber, self.span))
+                }
+            },
+            ExprKind::Name(name) => {
+                match scope.get_value(name) {
+                    Some(value) => Oper::Operand(value),
+                    None => return Err(CalcError::with_span(CalcErrorType::UnknownName, self.span))
+                }
+            },
+            ExprKind::Result(number) => {
+                match scope.context.get_result(*number) {
+                    Some(value) => Oper::Operand(value.to_arithmetic(scope.context.arithmetic())),
+                    None => return Err(CalcError::with_span(CalcErrorType::UnknownName, self.span))
+                }
+            },
+            ExprKind::Unary(oper, operand) => {
+                operand.to_rpn_nested(rpn, scope, nesting + 1)?;
+                oper.clone()
+            },
+            ExprKind::Binary(oper, left, right) => {
+                left.to_rpn_nested(rpn, scope, nesting + 1)?;
+                right.to_rpn_nested(rpn, scope, nesting + 1)?;
+                oper.clone()
+            },
+            ExprKind::Call(name, args) => {
+                // User functions shadow built-in ones
+                if let Some(function) = scope.context.get_function(name) {
+                    Oper::Operand(self.call_user_function(function, args, scope, nesting + 1)?)
+                }
+                else {
+                    let function = match Function::from_name(name) {
+                        Some(function) => function,
+                        None => return Err(CalcError::with_span(CalcErrorType::UnknownFunction, self.span))
+                    };
+                    if !function.is_arguments_count_correct(args.len()) {
+                        return Err(CalcError::with_span(CalcErrorType::WrongArgumentsCount, self.span))
+                    }
+
+                    for arg in args {
+                        arg.to_rpn_nested(rpn, scope, nesting + 1)?;
+                    }
+                    Oper::Call(function, args.len())
+                }
+            }
+        };
+
+        rpn.push(RpnItem { oper, span: self.span });
+        Ok(())
+    }
+
+    /// Calculates arguments, binds them to parameters and calculates the body.
+    /// The body is not a part of the input, so errors inside it point at the call.
+    fn call_user_function(&self, function: &UserFunction, args: &[Expr], scope: &Scope, nesting: usize) -> Result<Number, CalcError> {
+        if function.parameters.len() != args.len() {
+            return Err(CalcError::with_span(CalcErrorType::WrongArgumentsCount, self.span))
+        }
+        if scope.depth >= MAX_CALL_DEPTH {
+            return Err(CalcError::with_span(CalcErrorType::RecursionTooDeep, self.span))
+        }
+        if !scope.context.take_step() {
+            return Err(CalcError::with_span(CalcErrorType::LimitExceeded, self.span))
+        }
+
+        let mut arguments: Vec<(String, Number)> = Vec::new();
+        for (parameter, arg) in function.parameters.iter().zip(args) {
+            let mut arg_rpn: Vec<RpnItem> = Vec::new();
+            arg.to_rpn_nested(&mut arg_rpn, scope, nesting)?;
+            arguments.push((parameter.clone(), calculate(&arg_rpn, scope.context)?));
+        }
+
+        let body_scope = Scope { context: scope.context, arguments, depth: scope.depth + 1, nesting };
+        let mut body_rpn: Vec<RpnItem> = Vec::new();
+
+        function.body.to_rpn(&mut body_rpn, &body_scope)
+            .and_then(|_| calculate(&body_rpn, scope.context))
+            .map_err(|e| CalcError::with_span(e.error_type(), self.span))
+    }
+}
+
+/// Builds a statement from tokens: `name = expression` is an assignment,
+/// `name(parameter, ...) = expression` is a definition of a function, `expression in hex` is a conversion,
+/// anything else is an expression
+pub fn parse_statement(tokens: &[Token], context: &Context) -> Result<Statement, CalcError> {
+    let equals = tokens.iter().position(|token| token.kind == TokenKind::Equals);
+
+    match (tokens, equals) {
+        ([Token { kind: TokenKind::Identifier(name), .. }, Token { kind: TokenKind::Equals, span }, rest @ ..], _) => {
+            if rest.is_empty() { return Err(CalcError::with_span(CalcErrorType::MissedOperand, Span { start: span.end, end: span.end })) }
+            Ok(Statement::Assignment(name.clone(), parse(rest, context)?))
+        },
+        ([Token { kind: TokenKind::Identifier(name), .. }, Token { kind: TokenKind::LeftBracket, .. }, ..], Some(equals)) => {
+            let parameters = parse_parameters(&tokens[1..equals])?;
+            let rest = &tokens[equals + 1..];
+            if rest.is_empty() {
+                let end = tokens[equals].span.end;
+                return Err(CalcError::with_span(CalcErrorType::MissedOperand, Span { start: end, end }))
+            }
+            Ok(Statement::Definition(name.clone(), parameters, parse(rest, context)?))
+        },
+        _ => match parse_base(tokens)? {
+            Some((expression, radix)) => Ok(Statement::Conversion(parse(expression, context)?, radix)),
+            None => Ok(Statement::Expression(parse(tokens, context)?))
+        }
+    }
+}
+
+/// Splits `expression in hex` or `expression in base 5` into the expression and the base.
+/// Other names after `in` are a part of the expression, so `2 in` is still 2 * in.
+fn parse_base(tokens: &[Token]) -> Result<Option<(&[Token], u32)>, CalcError> {
+    let (expression, radix) = match tokens {
+        [expression @ .., Token { kind: TokenKind::Identifier(word), .. }, Token { kind: TokenKind::Identifier(name), .. }] if word == "in" => {
+            let radix = match name.as_str() {
+                "hex" | "hexadecimal" => 16,
+                "dec" | "decimal" => 10,
+                "oct" | "octal" => 8,
+                "bin" | "binary" => 2,
+                _ => return Ok(None)
+            };
+            (expression, radix)
+        },
+        [
+            expression @ ..,
+            Token { kind: TokenKind::Identifier(word), .. },
+            Token { kind: TokenKind::Identifier(base), .. },
+            Token { kind: TokenKind::Number(number), span }
+        ] if word == "in" && base == "base" => {
+            match number.parse() {
+                Ok(radix) if (2..=36).contains(&radix) => (expression, radix),
+                _ => return Err(CalcError::with_span(CalcErrorType::UnknownBase, *span))
+            }
+        },
+        _ => return Ok(None)
+    };
+
+    if expression.is_empty() {
+        let start = tokens[0].span.start;
+        return Err(CalcError::with_span(CalcErrorType::MissedOperand, Span { start, end: start }))
+    }
+    Ok(Some((expression, radix)))
+}
+
+/// Parses names of parameters in brackets: `(x, y)`. Parameters must be different names.
+fn parse_parameters(tokens: &[Token]) -> Result<Vec<String>, CalcError> {
+    let mut parameters: Vec<String> = Vec::new();
+    let wrong_definition = |span: Span| CalcError::with_span(CalcErrorType::WrongDefinition, span);
+
+    let inside = match tokens {
+        [_, inside @ .., right_bracket] if right_bracket.kind == TokenKind::RightBracket => inside,
+        [.., last] => return Err(wrong_definition(last.span)),
+        [] => return Ok(parameters)
+    };
+
+    // Names on even places, commas on odd ones
+    for (i, token) in inside.iter().enumerate() {
+        match &token.kind {
+            TokenKind::Identifier(name) if i % 2 == 0 && !parameters.contains(name) => parameters.push(name.clone()),
+            TokenKind::Comma if i % 2 == 1 => {},
+            _ => return Err(wrong_definition(token.span))
+        }
+    }
+    if let Some(comma) = inside.last().filter(|token| token.kind == TokenKind::Comma) {
+        return Err(wrong_definition(comma.span))
+    }
+
+    Ok(parameters)
+}
+
+/// Builds an expression tree from tokens with the syntax and the limits of the context
+pub fn parse(tokens: &[Token], context: &Context) -> Result<Expr, CalcError> {
+    if tokens.is_empty() { return Err(CalcError::new(CalcErrorType::NotMathExpr)) }
+
+    let mut parser = Parser {
+        tokens,
+        position: 0,
+        depth: 0,
+        max_depth: context.limits().max_nesting_depth,
+        implicit_multiplication: !context.is_strict()
+    };
+    let expr = parser.parse_expr(0)?;
+
+    // Everything must be parsed, otherwise something stands after the expression without an operation
+    match parser.peek() {
+        None => Ok(expr),
+        Some(token) if token.kind == TokenKind::RightBracket => Err(CalcError::with_span(CalcErrorType::BracketsNotAgreed, token.span)),
+        // Words in a row are just a text
+        Some(Token { kind: TokenKind::Identifier(_), .. }) if matches!(expr.kind, ExprKind::Name(_)) => Err(CalcError::new(CalcErrorType::NotMathExpr)),
+        Some(token) => Err(CalcError::with_span(CalcErrorType::MissedOperation, token.span))
+    }
+}
+
+struct Parser<'a> {
+    tokens: &'a [Token],
+    position: usize,
+    /// How many expressions are parsed one inside another: in brackets, after unary operations, in powers and so on
+    depth: usize,
+    max_depth: usize,
+    /// Multiplication can be omitted: 2x, 2(3 + 4)
+    implicit_multiplication: bool
+}
+
+impl<'a> Parser<'a> {
+    fn peek(&self) -> Option<&'a Token> {
+        self.tokens.get(self.position)
+    }
+
+    fn advance(&mut self) {
+        self.position += 1;
+    }
+
+    /// Empty span right after the last token, there is something missed at the end
+    fn end_span(&self) -> Span {
+        let end = self.tokens.last().map_or(0, |token| token.span.end);
+        Span { start: end, end }
+    }
+
+    /// Precedence climbing: parses an expression from operations with priority not less than the given one
+    /// Multiplication is implicit between two operands, if it can't be anything else.
+    fn parse_expr(&mut self, min_priority: u8) -> Result<Expr, CalcError> {
+        if self.depth == self.max_depth {
+            let span = self.peek().map_or_else(|| self.end_span(), |token| token.span);
+            return Err(CalcError::with_span(CalcErrorType::LimitExceeded, span))
+        }
+        self.depth += 1;
+
+        let mut left = self.parse_operand()?;
+
+        while let Some(token) = self.peek() {
+            let (oper, is_implicit) = match binary_oper(&token.kind) {
+                Some(oper) => (oper, false),
+                None if self.is_implicit_multiplication() => (Oper::Mult, true),
+                None => break
+            };
+            if oper.get_priority() < min_priority { break }
+            if !is_implicit { self.advance(); }
+
+            // The right operand of a left-associative operation may contain only operations with a higher priority,
+            // of a right-associative one - also operations with the same priority
+            let right_priority = if oper.is_right_associative() { oper.get_priority() } else { oper.get_priority() + 1 };
+            let right = self.parse_expr(right_priority)?;
+            let span = Span { start: left.span.start, end: right.span.end };
+            left = Expr { kind: ExprKind::Binary(oper, Box::new(left), Box::new(right)), span };
+        }
+
+        self.depth -= 1;
+        Ok(left)
+    }
+
+    /// Multiplication can be omitted after a number, a result, a factorial or a closing bracket before a name
+    /// or an opening bracket, and after a closing bracket before a number too: 2x, 3pi, 2(3 + 4), (1 + 2)(3 + 4), (1 + 2)3.
+    /// It has the priority of the usual multiplication: 2^3x = 2^3 * x.
+    /// A name before a bracket is a function call, names in a row are a text and numbers in a row are a mistake.
+    fn is_implicit_multiplication(&self) -> bool {
+        let previous = match self.position.checked_sub(1).and_then(|i| self.tokens.get(i)) {
+            Some(previous) if self.implicit_multiplication => previous,
+            _ => return false
+        };
+
+        matches!(
+            (&previous.kind, self.peek().map(|token| &token.kind)),
+            (TokenKind::RightBracket, Some(TokenKind::Number(_) | TokenKind::Imaginary(_) | TokenKind::Result(_)))
+            | (
+                TokenKind::Number(_) | TokenKind::Imaginary(_) | TokenKind::Result(_) | TokenKind::Bang | TokenKind::RightBracket,
+                Some(TokenKind::Identifier(_) | TokenKind::LeftBracket)
+            )
+        )
+    }
+
+    /// Parses a number, a name, a function call, an expression in brackets or a unary operation with its operand.
+    /// Factorials bind tighter than any other operation: -3! = -(3!), 2^3! = 2^(3!)
+    fn parse_operand(&mut self) -> Result<Expr, CalcError> {
+        let token = match self.peek() {
+            Some(token) => token,
+            None => return Err(CalcError::with_span(CalcErrorType::MissedOperand, self.end_span()))
+        };
+
+        let mut operand = match &token.kind {
+            TokenKind::Number(n) => {
+                self.advance();
+                Expr { kind: ExprKind::Number(n.clone()), span: token.span }
+            },
+            TokenKind::Imaginary(n) => {
+                self.advance();
+                Expr { kind: ExprKind::Imaginary(n.clone()), span: token.span }
+            },
+            TokenKind::Result(number) => {
+                self.advance();
+                Expr { kind: ExprKind::Result(*number), span: token.span }
+            },
+            TokenKind::Identifier(name) => {
+                self.advance();
+
+                // A name right before a bracket is a function call
+                match self.peek() {
+                    Some(bracket) if bracket.kind == TokenKind::LeftBracket => {
+                        self.advance();
+                        let (args, end) = self.parse_arguments(bracket)?;
+                        Expr { kind: ExprKind::Call(name.clone(), args), span: Span { start: token.span.start, end } }
+                    },
+                    _ => Expr { kind: ExprKind::Name(name.clone()), span: token.span }
+                }
+            },
+            TokenKind::Plus | TokenKind::Minus | TokenKind::Tilde => {
+                self.advance();
+                let oper = match token.kind {
+                    TokenKind::Minus => Oper::Neg,
+                    TokenKind::Tilde => Oper::BitNot,
+                    _ => Oper::Pos
+                };
+
+                // Unary operations are right-associative, so the operand may contain operations with the same priority
+                let operand = self.parse_expr(oper.get_priority())?;
+                let span = Span { start: token.span.start, end: operand.span.end };
+                return Ok(Expr { kind: ExprKind::Unary(oper, Box::new(operand)), span })
+            },
+            TokenKind::LeftBracket => {
+                self.advance();
+                let inner = self.parse_expr(0)?;
+
+                match self.peek() {
+                    Some(right_bracket) if right_bracket.kind == TokenKind::RightBracket => {
+                        self.advance();
+                        Expr { kind: inner.kind, span: Span { start: token.span.start, end: right_bracket.span.end } }
+                    },
+                    Some(other) => return Err(CalcError::with_span(CalcErrorType::MissedOperation, other.span)),
+                    None => return Err(CalcError::with_span(CalcErrorType::BracketsNotAgreed, token.span))
+                }
+            },
+            _ => return Err(CalcError::with_span(CalcErrorType::MissedOperand, token.span))
+        };
+
+        // Every factorial is one more level of the tree
+        let mut factorials = 0;
+        while let Some(bang) = self.peek().filter(|token| token.kind == TokenKind::Bang) {
+            if self.depth + factorials == self.max_depth {
+                return Err(CalcError::with_span(CalcErrorType::LimitExceeded, bang.span))
+            }
+            factorials += 1;
+            self.advance();
+            let span = Span { start: operand.span.start, end: bang.span.end };
+            operand = Expr { kind: ExprKind::Unary(Oper::Fact, Box::new(operand)), span };
+        }
+
+        Ok(operand)
+    }
+
+    /// Parses comma-separated arguments of a function call after the opening bracket.
+    /// Returns the arguments and the end of the closing bracket.
+    fn parse_arguments(&mut self, left_bracket: &Token) -> Result<(Vec<Expr>, usize), CalcError> {
+        let mut args: Vec<Expr> = Vec::new();
+
+        if let Some(right_bracket) = self.peek().filter(|token| token.kind == TokenKind::RightBracket) {
+            self.advance();
+            return Ok((args, right_bracket.span.end))
+        }
+
+        loop {
+            args.push(self.parse_expr(0)?);
+
+            match self.peek() {
+                Some(comma) if comma.kind == TokenKind::Comma => self.advance(),
+                Some(right_bracket) if right_bracket.kind == TokenKind::RightBracket => {
+                    self.advance();
+                    return Ok((args, right_bracket.span.end))
+                },
+                Some(other) => return Err(CalcError::with_span(CalcErrorType::MissedOperation, other.span)),
+                None => return Err(CalcError::with_span(CalcErrorType::BracketsNotAgreed, left_bracket.span))
+            }
+        }
+    }
+}
+
+fn binary_oper(kind: &TokenKind) -> Option<Oper> {
+    match kind {
+        TokenKind::Plus => Some(Oper::Add),
+        TokenKind::Minus => Some(Oper::Sub),
+        TokenKind::Star => Some(Oper::Mult),
+        TokenKind::Slash => Some(Oper::Div),
+        TokenKind::DoubleSlash => Some(Oper::IntDiv),
+        TokenKind::Percent => Some(Oper::Rem),
+        TokenKind::Caret => Some(Oper::Exp),
+        TokenKind::Ampersand => Some(Oper::BitAnd),
+        TokenKind::Pipe => Some(Oper::BitOr),
+        TokenKind::ShiftLeft => Some(Oper::Shl),
+        TokenKind::ShiftRight => Some(Oper::Shr),
+        // `xor` is a word, so `^` stays a power
+        TokenKind::Identifier(name) if name == "xor" => Some(Oper::Xor),
+        _ => None
+    }
+}
+
+#[cfg(test)]
+mod tests {
+    use super::super::lexer::tokenize;
+    use super::super::locale::Locale;
+
+    #[test]
+    fn parse_number() {
+        use super::*;
+
+        let expr = parse(&tokenize(" 42 ", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(expr, Expr { kind: ExprKind::Number(String::from("42")), span: Span { start: 1, end: 3 } });
+    }
+
+    #[test]
+    fn parse_binary_tree() {
+        use super::*;
+
+        let expr = parse(&tokenize("1 + 2 * 3", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let expected = Expr {
+            kind: ExprKind::Binary(
+                Oper::Add,
+                Box::new(Expr { kind: ExprKind::Number(String::from("1")), span: Span { start: 0, end: 1 } }),
+                Box::new(Expr {
+                    kind: ExprKind::Binary(
+                        Oper::Mult,
+                        Box::new(Expr { kind: ExprKind::Number(String::from("2")), span: Span { start: 4, end: 5 } }),
+                        Box::new(Expr { kind: ExprKind::Number(String::from("3")), span: Span { start: 8, end: 9 } })
+                    ),
+                    span: Span { start: 4, end: 9 }
+                })
+            ),
+            span: Span { start: 0, end: 9 }
+        };
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn parse_brackets_span() {
+        use super::*;
+
+        let expr = parse(&tokenize("-(1 + 2)", Locale::default()).unwrap(), &Context::default()).unwrap();
+        match expr.kind {
+            ExprKind::Unary(Oper::Neg, operand) => assert_eq!(operand.span, Span { start: 1, end: 8 }),
+            _ => panic!("expected a unary negation")
+        }
+    }
+
+    #[test]
+    fn parse_empty() {
+        use super::*;
+
+        assert_eq!(parse(&[], &Context::default()), Err(CalcError::new(CalcErrorType::NotMathExpr)));
+    }
+
+    #[test]
+    fn parse_missed_operand() {
+        use super::*;
+
+        for message in ["2 +", "* 3", "2 + * 3", "()", "(2 -)", "2 ++"] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperand), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_missed_operation() {
+        use super::*;
+
+        for message in ["2 3", "2i 3", "(2 3)", "2 3x"] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation), "{}", message);
+        }
+
+        let mut context = Context::default();
+        context.set_strict(true);
+        for message in ["2 (3)", "(2) 3", "(2)(3)", "2x", "3!x"] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &context).map_err(|e| e.error_type()), Err(CalcErrorType::MissedOperation), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_implicit_multiplication() {
+        use super::*;
+
+        let name = |name: &str, start| Box::new(Expr { kind: ExprKind::Name(String::from(name)), span: Span { start, end: start + name.len() } });
+        let number = |text: &str, start| Box::new(Expr { kind: ExprKind::Number(String::from(text)), span: Span { start, end: start + text.len() } });
+
+        // The power is calculated first: 2^3x = 2^3 * x
+        let expr = parse(&tokenize("2^3x", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let power = Box::new(Expr { kind: ExprKind::Binary(Oper::Exp, number("2", 0), number("3", 2)), span: Span { start: 0, end: 3 } });
+        assert_eq!(expr, Expr { kind: ExprKind::Binary(Oper::Mult, power, name("x", 3)), span: Span { start: 0, end: 4 } });
+
+        // Minus belongs to the number: -2x = (-2) * x
+        let expr = parse(&tokenize("-2x", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let minus_two = Box::new(Expr { kind: ExprKind::Unary(Oper::Neg, number("2", 1)), span: Span { start: 0, end: 2 } });
+        assert_eq!(expr, Expr { kind: ExprKind::Binary(Oper::Mult, minus_two, name("x", 2)), span: Span { start: 0, end: 3 } });
+
+        // A name before a bracket is a call
+        let expr = parse(&tokenize("2sin(x)", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let call = Box::new(Expr { kind: ExprKind::Call(String::from("sin"), vec![*name("x", 5)]), span: Span { start: 1, end: 7 } });
+        assert_eq!(expr, Expr { kind: ExprKind::Binary(Oper::Mult, number("2", 0), call), span: Span { start: 0, end: 7 } });
+    }
+
+    #[test]
+    fn parse_brackets_not_agreed() {
+        use super::*;
+
+        for message in ["(2 + 3", "2 + 3)", "((2)"] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| e.error_type()), Err(CalcErrorType::BracketsNotAgreed), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_error_spans() {
+        use super::*;
+
+        for (message, span) in [
+            ("2 + * 3", Span { start: 4, end: 5 }),
+            ("2 +", Span { start: 3, end: 3 }),
+            ("2 3", Span { start: 2, end: 3 }),
+            ("(2 + 3", Span { start: 0, end: 1 }),
+            ("2 + 3)", Span { start: 5, end: 6 })
+        ] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| e.span()), Err(Some(span)), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_function_call() {
+        use super::*;
+
+        let expr = parse(&tokenize("log(2, x)", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let expected = Expr {
+            kind: ExprKind::Call(String::from("log"), vec![
+                Expr { kind: ExprKind::Number(String::from("2")), span: Span { start: 4, end: 5 } },
+                Expr { kind: ExprKind::Name(String::from("x")), span: Span { start: 7, end: 8 } }
+            ]),
+            span: Span { start: 0, end: 9 }
+        };
+        assert_eq!(expr, expected);
+    }
+
+    #[test]
+    fn parse_function_call_without_arguments() {
+        use super::*;
+
+        let expr = parse(&tokenize("f()", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(expr, Expr { kind: ExprKind::Call(String::from("f"), Vec::new()), span: Span { start: 0, end: 3 } });
+    }
+
+    #[test]
+    fn parse_function_call_errors() {
+        use super::*;
+
+        for (message, error_type) in [
+            ("max(1, )", CalcErrorType::MissedOperand),
+            ("max(1 2)", CalcErrorType::MissedOperation),
+            ("max(1, 2", CalcErrorType::BracketsNotAgreed),
+            ("sqrt 2", CalcErrorType::MissedOperation),
+            ("just a text", CalcErrorType::NotMathExpr)
+        ] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| e.error_type()), Err(error_type), "{}", message);
+        }
+    }
+
+    #[test]
+    fn to_rpn_resolves_names() {
+        use super::*;
+
+        let mut rpn: Vec<RpnItem> = Vec::new();
+        let result = parse(&tokenize("sqrt(2, 3)", Locale::default()).unwrap(), &Context::default()).unwrap().to_rpn(&mut rpn, &Scope::new(&Context::default()));
+        assert_eq!(result.map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::WrongArgumentsCount, Some(Span { start: 0, end: 10 }))));
+
+        let result = parse(&tokenize("1 + foo(3)", Locale::default()).unwrap(), &Context::default()).unwrap().to_rpn(&mut rpn, &Scope::new(&Context::default()));
+        assert_eq!(result.map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::UnknownFunction, Some(Span { start: 4, end: 10 }))));
+
+        let result = parse(&tokenize("1 + x", Locale::default()).unwrap(), &Context::default()).unwrap().to_rpn(&mut rpn, &Scope::new(&Context::default()));
+        assert_eq!(result.map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::UnknownName, Some(Span { start: 4, end: 5 }))));
+    }
+
+    #[test]
+    fn parse_nesting_depth() {
+        use super::*;
+
+        use super::super::limits::Limits;
+
+        // The right operand of an operation is one level deeper too
+        let mut context = Context::default();
+        context.set_limits(Limits { max_nesting_depth: 4, ..Limits::default() });
+        assert!(parse(&tokenize("(1 + 2) * 2^3", Locale::default()).unwrap(), &context).is_ok());
+        assert!(parse(&tokenize("1 + 2 + 3 + 4 + 5", Locale::default()).unwrap(), &context).is_ok());
+        for (message, span) in [
+            ("((((1))))", Span { start: 4, end: 5 }),
+            ("---+1", Span { start: 4, end: 5 }),
+            ("2^2^2^2^2", Span { start: 8, end: 9 }),
+            ("3!!!!", Span { start: 4, end: 5 }),
+            ("((1 + (", Span { start: 7, end: 7 })
+        ] {
+            assert_eq!(parse(&tokenize(message, Locale::default()).unwrap(), &context).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::LimitExceeded, Some(span))), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_factorial() {
+        use super::*;
+
+        let expr = parse(&tokenize("-3!!", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let three = Expr { kind: ExprKind::Number(String::from("3")), span: Span { start: 1, end: 2 } };
+        let factorial = Expr { kind: ExprKind::Unary(Oper::Fact, Box::new(three)), span: Span { start: 1, end: 3 } };
+        let factorial = Expr { kind: ExprKind::Unary(Oper::Fact, Box::new(factorial)), span: Span { start: 1, end: 4 } };
+        assert_eq!(expr, Expr { kind: ExprKind::Unary(Oper::Neg, Box::new(factorial)), span: Span { start: 0, end: 4 } });
+
+        assert_eq!(parse(&tokenize("!3", Locale::default()).unwrap(), &Context::default()).map_err(|e| (e.error_type(), e.span())), Err((CalcErrorType::MissedOperand, Some(Span { start: 0, end: 1 }))));
+    }
+
+    #[test]
+    fn parse_bitwise_priority() {
+        use super::*;
+
+        let to_rpn = |message: &str| {
+            let mut rpn: Vec<RpnItem> = Vec::new();
+            parse(&tokenize(message, Locale::default()).unwrap(), &Context::default()).unwrap().to_rpn(&mut rpn, &Scope::new(&Context::default())).unwrap();
+            rpn.into_iter().map(|item| item.oper).collect::<Vec<Oper>>()
+        };
+        assert_eq!(to_rpn("1 | 2 xor 3 & 4 << 5 + 6"), to_rpn("1 | (2 xor (3 & (4 << (5 + 6))))"));
+        assert_eq!(to_rpn("1 & 2 | 3 xor 4"), to_rpn("(1 & 2) | (3 xor 4)"));
+        assert_eq!(to_rpn("~2^3 // 4"), to_rpn("(~(2^3)) // 4"));
+    }
+
+    #[test]
+    fn parse_statement_assignment() {
+        use super::*;
+
+        let statement = parse_statement(&tokenize("x = 2", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(statement, Statement::Assignment(String::from("x"), Expr { kind: ExprKind::Number(String::from("2")), span: Span { start: 4, end: 5 } }));
+
+        let statement = parse_statement(&tokenize("x", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(statement, Statement::Expression(Expr { kind: ExprKind::Name(String::from("x")), span: Span { start: 0, end: 1 } }));
+    }
+
+    #[test]
+    fn parse_statement_errors() {
+        use super::*;
+
+        for (message, error_type) in [
+            ("x =", CalcErrorType::MissedOperand),
+            ("x = = 2", CalcErrorType::MissedOperand),
+            ("2 = 2", CalcErrorType::MissedOperation),
+            ("x + 1 = 2", CalcErrorType::MissedOperation)
+        ] {
+            assert_eq!(parse_statement(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| e.error_type()), Err(error_type), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_statement_definition() {
+        use super::*;
+
+        let statement = parse_statement(&tokenize("f(x, y) = y", Locale::default()).unwrap(), &Context::default()).unwrap();
+        let parameters = vec![String::from("x"), String::from("y")];
+        assert_eq!(statement, Statement::Definition(String::from("f"), parameters, Expr { kind: ExprKind::Name(String::from("y")), span: Span { start: 10, end: 11 } }));
+
+        let statement = parse_statement(&tokenize("f() = 1", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(statement, Statement::Definition(String::from("f"), Vec::new(), Expr { kind: ExprKind::Number(String::from("1")), span: Span { start: 6, end: 7 } }));
+    }
+
+    #[test]
+    fn parse_statement_conversion() {
+        use super::*;
+
+        let statement = parse_statement(&tokenize("255 in hex", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(statement, Statement::Conversion(Expr { kind: ExprKind::Number(String::from("255")), span: Span { start: 0, end: 3 } }, 16));
+
+        let statement = parse_statement(&tokenize("x in base 36", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert_eq!(statement, Statement::Conversion(Expr { kind: ExprKind::Name(String::from("x")), span: Span { start: 0, end: 1 } }, 36));
+
+        // Other names after `in` are multiplied
+        let statement = parse_statement(&tokenize("2 in", Locale::default()).unwrap(), &Context::default()).unwrap();
+        assert!(matches!(statement, Statement::Expression(Expr { kind: ExprKind::Binary(Oper::Mult, _, _), .. })));
+
+        for (message, error_type, span) in [
+            ("in hex", CalcErrorType::MissedOperand, Span { start: 0, end: 0 }),
+            ("2 in base 37", CalcErrorType::UnknownBase, Span { start: 10, end: 12 }),
+            ("2 in base 1", CalcErrorType::UnknownBase, Span { start: 10, end: 11 })
+        ] {
+            assert_eq!(parse_statement(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| (e.error_type(), e.span())), Err((error_type, Some(span))), "{}", message);
+        }
+    }
+
+    #[test]
+    fn parse_statement_definition_errors() {
+        use super::*;
+
+        for (message, error_type, span) in [
+            ("f(x) =", CalcErrorType::MissedOperand, Span { start: 6, end: 6 }),
+            ("f(x, x) = x", CalcErrorType::WrongDefinition, Span { start: 5, end: 6 }),
+            ("f(x, 2) = x", CalcErrorType::WrongDefinition, Span { start: 5, end: 6 }),
+            ("f(x y) = x", CalcErrorType::WrongDefinition, Span { start: 4, end: 5 }),
+            ("f(x,) = x", CalcErrorType::WrongDefinition, Span { start: 3, end: 4 }),
+            ("f(x) + 1 = x", CalcErrorType::WrongDefinition, Span { start: 7, end: 8 })
+        ] {
+            assert_eq!(parse_statement(&tokenize(message, Locale::default()).unwrap(), &Context::default()).map_err(|e| (e.error_type(), e.span())), Err((error_type, Some(span))), "{}", message);
+        }
+    }
+
+    #[test]
+    fn to_rpn_variables_shadow_constants() {
+        use super::*;
+
+        let mut context = Context::default();
+        context.set_variable("e", Number::from(5.0));
+
+        let mut rpn: Vec<RpnItem> = Vec::new();
+        parse(&tokenize("e", Locale::default()).unwrap(), &Context::default()).unwrap().to_rpn(&mut rpn, &Scope::new(&context)).unwrap();
+        assert_eq!(rpn, vec![RpnItem { oper: Oper::Operand(Number::from(5.0)), span: Span { start: 0, end: 1 } }]);
+    }
+}
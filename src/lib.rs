@@ -0,0 +1,19 @@
+//! Calculator of mathematical expressions: `(2 + 2) * 2`, `sqrt(2)`, `x = 5!`, `f(x) = x^2 + 1`.
+//!
+//! Every expression is evaluated in a context, which keeps variables, user functions,
+//! previous results and settings between expressions:
+//!
+//! ```
+//! use calculate42::{evaluate, Answer, CalcContext, Number};
+//!
+//! let mut context = CalcContext::default();
+//! evaluate("x = 2 + 2", &mut context).unwrap();
+//! assert_eq!(evaluate("x * 2", &mut context), Ok(Answer::Number(Number::from(8))));
+//! ```
+
+mod calculate42;
+
+pub use calculate42::{
+    evaluate, Answer, Arithmetic, Base, CalcContext, CalcError, CalcErrorType, Constant, Digits, Format, Limits, Locale, Notation, Number, Span,
+    CONSTANTS, DEFAULT_PRECISION, MAX_PRECISION
+};
@@ -1,5 +1,27 @@
-use teloxide::{prelude2::*, utils::command::BotCommand};
-mod calculate42;
+use std::collections::HashMap;
+use std::sync::{Arc, Mutex, PoisonError};
+use std::time::Duration;
+use teloxide::{prelude2::*, types::ParseMode, utils::{command::BotCommand, html}};
+use calculate42::{Answer, Arithmetic, Base, CalcContext, Digits, Format, Limits, Locale, Notation, Number, DEFAULT_PRECISION, MAX_PRECISION};
+
+/// The widest integers in two's complement, so a binary result fits a message
+const MAX_WIDTH: u32 = 1024;
+
+/// Telegram doesn't send longer messages
+const MAX_MESSAGE_LENGTH: usize = 4096;
+
+/// Anyone can write to the bot, so one message must not take much time
+const LIMITS: Limits = Limits {
+    max_input_length: MAX_MESSAGE_LENGTH,
+    max_tokens: 1000,
+    max_nesting_depth: 50,
+    max_exponent: 1_000_000.0,
+    max_steps: 100_000,
+    max_time: Duration::from_secs(5)
+};
+
+/// Formatting and sending take time too, so the bot stops waiting for a calculation after this time
+const CALCULATION_TIMEOUT: Duration = Duration::from_secs(10);
 
 #[tokio::main]
 async fn main() {
@@ -7,36 +29,356 @@ async fn main() {
     log::info!("Starting dices_bot...");
 
     let bot = Bot::from_env().auto_send();
+    // Every chat has its own variables. The map is locked only to find the context of a chat,
+    // so a long calculation in one chat doesn't stop the others.
+    let contexts: Arc<Mutex<HashMap<i64, Arc<Mutex<CalcContext>>>>> = Arc::new(Mutex::new(HashMap::new()));
 
-    teloxide::repls2::repl(bot, |message: Message, bot: AutoSend<Bot>| async move {
-        let reply;
+    teloxide::repls2::repl(bot, move |message: Message, bot: AutoSend<Bot>| {
+        let contexts = contexts.clone();
 
-        match message.text() {
-            Some(t) => {
-                if let Ok(command) = Command::parse(t, "bot") {
-                    match command {
-                        Command::Help => reply = Command::descriptions()
-                    }
-                } 
-                else {
-                    match calculate42::try_calculate(&String::from(t)) {
-                        Ok(n) => { reply = format!("{}", n) },
-                        Err(e) => { reply = String::from(format!("{}", e)) }
+        async move {
+            let reply;
+            // Errors with a position are shown in a monospace block, so the caret stands under the problem place
+            let mut is_code = false;
+
+            match message.text() {
+                Some(t) => {
+                    // A panic in another chat doesn't break the map, so a poisoned lock is fine
+                    let context = contexts.lock().unwrap_or_else(PoisonError::into_inner)
+                        .entry(message.chat.id)
+                        .or_insert_with(|| {
+                            // Money calculations are more common in chats than fast ones, so numbers are decimal by default
+                            let mut context = CalcContext::with_arithmetic(Arithmetic::Decimal(DEFAULT_PRECISION));
+                            context.set_limits(LIMITS);
+                            // Numbers are written like in the language of the user, until the chat chooses another way
+                            if let Some(language) = message.from().and_then(|user| user.language_code.as_deref()) {
+                                context.set_locale(Locale::from_language(language));
+                            }
+                            Arc::new(Mutex::new(context))
+                        })
+                        .clone();
+
+                    // Calculations block the thread, so they don't run on the threads of the bot
+                    let text = String::from(t);
+                    let task = tokio::task::spawn_blocking(move || {
+                        answer(&text, &mut context.lock().unwrap_or_else(PoisonError::into_inner))
+                    });
+
+                    match tokio::time::timeout(CALCULATION_TIMEOUT, task).await {
+                        Ok(Ok((text, code))) => {
+                            reply = text;
+                            is_code = code;
+                        },
+                        Ok(Err(e)) => {
+                            log::error!("Calculation of {:?} failed: {}", t, e);
+                            reply = String::from("Something went wrong, try another expression.");
+                        },
+                        Err(_) => reply = String::from("The calculation takes too long.")
                     }
-                }
-            },
-            None => reply = String::from("Eh?")
+                },
+                None => reply = String::from("Eh?")
+            }
+            
+            if is_code {
+                bot.send_message(message.chat.id, html::code_block(&reply)).parse_mode(ParseMode::Html).await?;
+            }
+            else {
+                bot.send_message(message.chat.id, reply).await?;
+            }
+            respond(())
         }
-        
-        bot.send_message(message.chat.id, reply).await?;
-        respond(())
     })
     .await;
 }
 
+/// Runs a command or calculates an expression. The reply is code, if it is an error with a position.
+fn answer(text: &str, context: &mut CalcContext) -> (String, bool) {
+    if let Ok(command) = Command::parse(text, "bot") {
+        let reply = match command {
+            Command::Help => Command::descriptions(),
+            Command::Constants => constants_list(),
+            Command::Vars => variables_list(context),
+            Command::Mode(mode) => set_mode(context, &mode),
+            Command::Fractions(format) => set_fractions(context, &format),
+            Command::Complex(switch) => set_complex(context, &switch),
+            Command::Ieee(switch) => set_ieee(context, &switch),
+            Command::Strict(switch) => set_strict(context, &switch),
+            Command::Locale(example) => set_locale(context, &example),
+            Command::Precision(words) => set_precision(context, &words),
+            Command::Notation(notation) => set_notation(context, &notation),
+            Command::Grouping(switch) => set_grouping(context, &switch),
+            Command::Base(words) => set_base(context, &words),
+            Command::Clear => {
+                context.clear();
+                String::from("All variables, functions and results are removed.")
+            }
+        };
+        return (reply, false)
+    }
+
+    match calculate42::evaluate(text, context) {
+        Ok(Answer::Number(n)) => {
+            let format = context.format();
+            (show_result(context, n, format), false)
+        },
+        Ok(Answer::Formatted(n, format)) => (show_result(context, n, format), false),
+        Ok(Answer::Function(name)) => (format!("Function {} is defined.", name), false),
+        Err(e) => (e.render(text), e.span().is_some())
+    }
+}
+
 #[derive(BotCommand, Clone)]
-#[command(rename = "lowercase", description = "Bot can calculate any (almost) mathematical expression. Just type it, like that: (2 + 2) * 2. These commands are supported:")]
+#[command(rename = "lowercase", description = "Bot can calculate any (almost) mathematical expression. Just type it, like that: (2 + 2) * 2. Functions and constants are supported too: sqrt(2), sin(pi / 2), log(2, 8), max(1, 2, 3), 5! and others. Integers are exact: 2^200, 50!. Numbers can be written like 6.02e23, 1_000_000, 0xFF, 0o17 or 0b1011. Integers have bit operations: 0xF0 & 0x3C, 1 | 2, 5 xor 3, ~5, 1 << 8, 0xFF >> 4, and division rounded down: 7 // 2. Complex numbers are supported: sqrt(-4), (3 + 4i) * (1 - 2i). Results can be saved to variables: x = 2 + 2, and then used: x * 2 or 2x. Functions can be defined too: f(x, y) = x^2 + y, and then called: f(3, 1). The previous result is ans (or _), $1, $2... are the first, the second and so on results. These commands are supported:")]
 enum Command {
     #[command(description = "display this text.")]
-    Help
-}
\ No newline at end of file
+    Help,
+    #[command(description = "list constants, which can be used in expressions.")]
+    Constants,
+    #[command(description = "list variables and functions of this chat.")]
+    Vars,
+    #[command(description = "remove all variables, functions and results of this chat.")]
+    Clear,
+    #[command(description = "choose how numbers are calculated: /mode decimal (exact, 50 significant digits by default), /mode decimal 20, /mode rational (exact fractions: 1/3 + 1/6 = 1/2) or /mode float (fast, but 0.1 + 0.2 = 0.30000000000000004).")]
+    Mode(String),
+    #[command(description = "choose how fractions are shown: /fractions improper (3/2), /fractions mixed (1 1/2), add decimal to see the decimal value too: /fractions mixed decimal.")]
+    Fractions(String),
+    #[command(description = "choose, if operations like sqrt(-1) give complex numbers: /complex on (by default) or /complex off.")]
+    Complex(String),
+    #[command(description = "choose, if operations like 1/0 give inf and NaN like IEEE floats: /ieee on, or errors: /ieee off (by default).")]
+    Ieee(String),
+    #[command(description = "choose, if multiplication can be omitted, like 2x or 2(3 + 4): /strict off (by default), or must be written: /strict on.")]
+    Strict(String),
+    #[command(description = "choose how numbers are written: write one thousand two hundred thirty-four and a half your way, like /locale 1,234.5, /locale 1 234,5 or /locale 1'234.5, or use both dot and comma as decimal separators: /locale any.")]
+    Locale(String),
+    #[command(description = "choose how many digits of results are shown: /precision 4 (decimal places), /precision 4 significant, /precision all (by default), add trim to drop zeros at the end: /precision 4 trim.")]
+    Precision(String),
+    #[command(description = "choose how results are written: /notation plain (by default), /notation sci (1.2345e3) or /notation eng (12.345e3).")]
+    Notation(String),
+    #[command(description = "choose, if digits of results are grouped by three like in the locale: /grouping on (1 234 567) or /grouping off (by default).")]
+    Grouping(String),
+    #[command(description = "choose the base of results: /base 16, /base 2, any base from 2 to 36, or /base 10 (by default). Add the width in bits to see negative integers in two's complement: /base 16 32. One result can be converted too: 255 in hex, 5 in bin, 35 in base 36.")]
+    Base(String)
+}
+
+/// Renders the result and saves it, so the next expressions can use it
+fn show_result(context: &mut CalcContext, n: Number, format: Format) -> String {
+    let text = format.render(&n);
+    context.push_result(n);
+
+    if text.len() > MAX_MESSAGE_LENGTH {
+        format!("The result is too long to show, it has {} digits. It is saved, so you can use it: ans.",
+            text.chars().filter(char::is_ascii_alphanumeric).count())
+    }
+    else { text }
+}
+
+fn constants_list() -> String {
+    calculate42::CONSTANTS.iter()
+        .map(|constant| format!("{} = {} — {}", constant.name, constant.value, constant.description))
+        .collect::<Vec<String>>()
+        .join("\n")
+}
+
+/// Changes the arithmetic of the chat, or describes the current one, if there is no argument
+fn set_mode(context: &mut CalcContext, mode: &str) -> String {
+    let words: Vec<&str> = mode.split_whitespace().collect();
+
+    let arithmetic = match words.as_slice() {
+        [] => return describe_mode(context.arithmetic()),
+        ["float"] => Arithmetic::Float,
+        ["decimal"] => Arithmetic::Decimal(DEFAULT_PRECISION),
+        ["decimal", digits] => match digits.parse() {
+            Ok(digits) if (1..=MAX_PRECISION).contains(&digits) => Arithmetic::Decimal(digits),
+            _ => return format!("Precision must be a number from 1 to {}.", MAX_PRECISION)
+        },
+        ["rational"] => Arithmetic::Rational,
+        _ => return String::from("Unknown mode. Use /mode decimal, /mode decimal 20, /mode rational or /mode float.")
+    };
+
+    context.set_arithmetic(arithmetic);
+    describe_mode(arithmetic)
+}
+
+fn describe_mode(arithmetic: Arithmetic) -> String {
+    match arithmetic {
+        Arithmetic::Float => String::from("Numbers are floats."),
+        Arithmetic::Decimal(digits) => format!("Numbers are decimal with {} significant digits.", digits),
+        Arithmetic::Rational => String::from("Numbers are exact fractions.")
+    }
+}
+
+/// Changes how fractions are shown, or describes the current way, if there are no arguments
+fn set_fractions(context: &mut CalcContext, words: &str) -> String {
+    let mut format = context.format();
+
+    if !words.trim().is_empty() {
+        format.mixed_fractions = false;
+        format.fraction_decimal = false;
+
+        for word in words.split_whitespace() {
+            match word {
+                "improper" => format.mixed_fractions = false,
+                "mixed" => format.mixed_fractions = true,
+                "decimal" => format.fraction_decimal = true,
+                _ => return String::from("Unknown way. Use /fractions improper, /fractions mixed, /fractions mixed decimal.")
+            }
+        }
+        context.set_format(format);
+    }
+
+    describe_fractions(format)
+}
+
+fn describe_fractions(format: Format) -> String {
+    let three_halves = Number::from(3).div(&Number::from(2), Arithmetic::Rational);
+
+    format!("Fractions are shown like that: {}", format.render(&three_halves))
+}
+
+/// Turns complex results on or off, or describes the current choice, if there is no argument
+fn set_complex(context: &mut CalcContext, switch: &str) -> String {
+    match switch.trim() {
+        "" => {},
+        "on" => context.set_real_only(false),
+        "off" => context.set_real_only(true),
+        _ => return String::from("Unknown switch. Use /complex on or /complex off.")
+    }
+
+    if context.is_real_only() { String::from("Operations like sqrt(-1) have no result.") }
+    else { String::from("Operations like sqrt(-1) give complex numbers: i.") }
+}
+
+/// Turns IEEE results on or off, or describes the current choice, if there is no argument
+fn set_ieee(context: &mut CalcContext, switch: &str) -> String {
+    match switch.trim() {
+        "" => {},
+        "on" => context.set_ieee(true),
+        "off" => context.set_ieee(false),
+        _ => return String::from("Unknown switch. Use /ieee on or /ieee off.")
+    }
+
+    if context.is_ieee() { String::from("Operations like 1/0 give inf and NaN.") }
+    else { String::from("Operations like 1/0 are errors.") }
+}
+
+/// Turns the strict syntax on or off, or describes the current choice, if there is no argument
+fn set_strict(context: &mut CalcContext, switch: &str) -> String {
+    match switch.trim() {
+        "" => {},
+        "on" => context.set_strict(true),
+        "off" => context.set_strict(false),
+        _ => return String::from("Unknown switch. Use /strict on or /strict off.")
+    }
+
+    if context.is_strict() { String::from("Multiplication must be written: 2 * x.") }
+    else { String::from("Multiplication can be omitted: 2x.") }
+}
+
+/// Changes how numbers are written, or describes the current way, if there is no example
+fn set_locale(context: &mut CalcContext, example: &str) -> String {
+    let example = example.trim();
+    if !example.is_empty() {
+        match example.parse() {
+            Ok(locale) => context.set_locale(locale),
+            Err(message) => return message
+        }
+    }
+
+    format!("Numbers are written like that: {}.", context.locale().example())
+}
+
+/// Changes how many digits of results are shown, or describes the current choice, if there are no arguments
+fn set_precision(context: &mut CalcContext, words: &str) -> String {
+    let mut format = context.format();
+    let mut words: Vec<&str> = words.split_whitespace().collect();
+
+    if !words.is_empty() {
+        format.trim_zeros = words.last() == Some(&"trim");
+        if format.trim_zeros { words.pop(); }
+
+        format.digits = match words.as_slice() {
+            [] => format.digits,
+            ["all"] => Digits::All,
+            [digits] => match digits.parse() {
+                Ok(digits) if digits <= MAX_PRECISION => Digits::Decimals(digits),
+                _ => return format!("Decimal places must be a number from 0 to {}.", MAX_PRECISION)
+            },
+            [digits, "significant" | "sig"] => match digits.parse() {
+                Ok(digits) if (1..=MAX_PRECISION).contains(&digits) => Digits::Significant(digits),
+                _ => return format!("Significant digits must be a number from 1 to {}.", MAX_PRECISION)
+            },
+            _ => return String::from("Unknown precision. Use /precision 4, /precision 4 significant, /precision all, and add trim to drop zeros at the end.")
+        };
+        context.set_format(format);
+    }
+
+    describe_format(format)
+}
+
+/// Changes the notation of results, or describes the current one, if there is no argument
+fn set_notation(context: &mut CalcContext, notation: &str) -> String {
+    let mut format = context.format();
+    match notation.trim() {
+        "" => {},
+        "plain" => format.notation = Notation::Plain,
+        "sci" | "scientific" => format.notation = Notation::Scientific,
+        "eng" | "engineering" => format.notation = Notation::Engineering,
+        _ => return String::from("Unknown notation. Use /notation plain, /notation sci or /notation eng.")
+    }
+
+    context.set_format(format);
+    describe_format(format)
+}
+
+/// Turns grouping of digits on or off, or describes the current choice, if there is no argument
+fn set_grouping(context: &mut CalcContext, switch: &str) -> String {
+    let mut format = context.format();
+    match switch.trim() {
+        "" => {},
+        // The same separator as in expressions, a space, if the locale has none
+        "on" => format.group_separator = Some(context.locale().group_separator.unwrap_or(' ')),
+        "off" => format.group_separator = None,
+        _ => return String::from("Unknown switch. Use /grouping on or /grouping off.")
+    }
+
+    context.set_format(format);
+    describe_format(format)
+}
+
+/// Changes the base of results, or describes the current one, if there are no arguments
+fn set_base(context: &mut CalcContext, words: &str) -> String {
+    let mut format = context.format();
+    let words: Vec<&str> = words.split_whitespace().collect();
+
+    let (radix, width) = match words.as_slice() {
+        [] => return describe_format(format),
+        [radix] => (radix.parse(), Ok(None)),
+        [radix, width] => (radix.parse(), width.parse().map(Some)),
+        _ => return String::from("Unknown base. Use /base 16 or /base 16 32.")
+    };
+    format.base = match (radix, width) {
+        (Ok(10), Ok(None)) => None,
+        (Ok(radix), Ok(width)) if (2..=36).contains(&radix) && width.is_none_or(|width| (1..=MAX_WIDTH).contains(&width)) => Some(Base { radix, width }),
+        (Ok(_), Ok(_)) => return format!("The base must be from 2 to 36, the width must be from 1 to {} bits.", MAX_WIDTH),
+        _ => return String::from("Unknown base. Use /base 16 or /base 16 32.")
+    };
+
+    context.set_format(format);
+    describe_format(format)
+}
+
+fn describe_format(format: Format) -> String {
+    let example = Number::parse("1234567.8", Arithmetic::Decimal(DEFAULT_PRECISION)).unwrap_or(Number::from(0));
+
+    format!("Results are shown like that: {}", format.render(&example))
+}
+
+fn variables_list(context: &CalcContext) -> String {
+    let variables: Vec<String> = context.variables()
+        .map(|(name, value)| format!("{} = {}", name, context.format().render(value)))
+        .chain(context.functions().map(String::from))
+        .collect();
+
+    if variables.is_empty() { String::from("There are no variables yet. Create one like that: x = 2 + 2") }
+    else { variables.join("\n") }
+}